@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::ops::{AddAssign, SubAssign};
 use std::str::FromStr;
 use std::{
@@ -9,59 +11,240 @@ use serde::de::Error as serdeError;
 use serde::Deserialize;
 use serde::Deserializer;
 
-type UnderlyingAmountType = i64;
+/// Integer type holding the value of an [`Amount`] in its smallest unit.
+///
+/// An i64 by default, the `i128` feature switches to a wider type for larger balances.
+#[cfg(not(feature = "i128"))]
+pub type UnderlyingAmountType = i64;
+/// Integer type holding the value of an [`Amount`] in its smallest unit.
+///
+/// An i128 as selected by the `i128` feature.
+#[cfg(feature = "i128")]
+pub type UnderlyingAmountType = i128;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+/// Fixed-point monetary amount.
+///
+/// Every amount carries its scale, the number of decimal places it is kept with. Amounts of
+/// different scales compare and combine exactly, arithmetic results have the larger of the
+/// two scales.
+#[derive(Clone, Copy, Debug)]
 pub struct Amount {
     value: UnderlyingAmountType,
+    scale: u32,
 }
 
-const DECIMAL_PLACES: u32 = 4;
-const AMOUNT_ONE: UnderlyingAmountType = (10 as UnderlyingAmountType).pow(DECIMAL_PLACES);
+/// Number of decimal places kept by an [`Amount`] unless given otherwise.
+pub const DECIMAL_PLACES: u32 = 4;
+/// Largest scale an [`Amount`] can have with the selected underlying type.
+pub const MAX_SCALE: u32 = UnderlyingAmountType::MAX.ilog10();
+
+/// Value of a whole unit at `scale`.
+const fn one(scale: u32) -> UnderlyingAmountType {
+    (10 as UnderlyingAmountType).pow(scale)
+}
 
 impl Amount {
+    /// Creates an amount from its value in ten-thousandths.
     pub fn new(value: UnderlyingAmountType) -> Self {
-        Self { value }
+        Self::with_scale(value, DECIMAL_PLACES)
+    }
+
+    /// Creates an amount from its value in units of the `scale`-th decimal place.
+    ///
+    /// # Panics
+    ///
+    /// If `scale` is larger than [`MAX_SCALE`].
+    pub fn with_scale(value: UnderlyingAmountType, scale: u32) -> Self {
+        assert!(
+            scale <= MAX_SCALE,
+            "amount scale {} exceeds {}",
+            scale,
+            MAX_SCALE
+        );
+        Self { value, scale }
+    }
+
+    /// Parses an amount with `scale` decimal places, handling further decimal places in the
+    /// input according to `excess`.
+    ///
+    /// # Panics
+    ///
+    /// If `scale` is larger than [`MAX_SCALE`].
+    pub fn parse(s: &str, scale: u32, excess: ExcessPrecision) -> Result<Self, ParseAmountError> {
+        let s = s.trim();
+        let (sign, unsigned) = match s.as_bytes().first() {
+            Some(b'-') => (-1, &s[1..]),
+            Some(b'+') => (1, &s[1..]),
+            _ => (1, s),
+        };
+        let (trunc_str, fract_str) = match unsigned.split_once('.') {
+            Some((trunc_str, fract_str)) => (trunc_str, fract_str),
+            None => (unsigned, ""),
+        };
+        if trunc_str.is_empty() && fract_str.is_empty() {
+            return Err(if unsigned.is_empty() {
+                ParseAmountError::Empty
+            } else {
+                ParseAmountError::InvalidDigit
+            });
+        }
+        if !fract_str.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseAmountError::InvalidDigit);
+        }
+        let (kept, dropped) = fract_str.split_at(fract_str.len().min(scale as usize));
+        if !dropped.is_empty() && excess == ExcessPrecision::Reject {
+            return Err(ParseAmountError::TooManyDecimalPlaces(scale));
+        }
+
+        let value = accumulate_digits(0, trunc_str, sign)?;
+        let value = accumulate_digits(value, kept, sign)?;
+        let mut value = value
+            .checked_mul(one(scale - kept.len() as u32))
+            .ok_or(ParseAmountError::Overflow)?;
+        if let ExcessPrecision::Round(mode) = excess {
+            value = mode
+                .round(value, sign < 0, Discarded::from_digits(dropped))
+                .ok_or(ParseAmountError::Overflow)?;
+        }
+        Ok(Amount::with_scale(value, scale))
+    }
+
+    /// Number of decimal places the amount is kept with.
+    pub fn scale(&self) -> u32 {
+        self.scale
     }
 
-    #[allow(dead_code)]
     pub fn trunc(&self) -> UnderlyingAmountType {
         self.trunc_fract().0
     }
 
-    #[allow(dead_code)]
     pub fn fract(&self) -> UnderlyingAmountType {
         self.trunc_fract().1
     }
 
+    /// Adds two amounts, returning `None` if the result does not fit.
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        let (a, b, scale) = align(self, other)?;
+        a.checked_add(b).map(|value| Self::with_scale(value, scale))
+    }
+
+    /// Subtracts two amounts, returning `None` if the result does not fit.
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        let (a, b, scale) = align(self, other)?;
+        a.checked_sub(b).map(|value| Self::with_scale(value, scale))
+    }
+
+    /// Converts the amount to `scale`, rounding according to `mode` if decimal places are
+    /// dropped. Returns `None` if the result does not fit.
+    ///
+    /// # Panics
+    ///
+    /// If `scale` is larger than [`MAX_SCALE`].
+    pub fn rescale(self, scale: u32, mode: RoundingMode) -> Option<Self> {
+        if scale >= self.scale {
+            return self.upscale(scale);
+        }
+        let divisor = one(self.scale - scale);
+        let remainder = (self.value % divisor).abs();
+        let discarded = match remainder.cmp(&(divisor / 2)) {
+            _ if remainder == 0 => Discarded::Zero,
+            Ordering::Less => Discarded::BelowHalf,
+            Ordering::Equal => Discarded::Half,
+            Ordering::Greater => Discarded::AboveHalf,
+        };
+        let value = mode.round(self.value / divisor, self.value < 0, discarded)?;
+        Some(Self::with_scale(value, scale))
+    }
+
+    /// Returns `percent` percent of the amount exactly, at a scale large enough to keep all
+    /// decimal places. Returns `None` if the result does not fit.
+    pub fn checked_percent(self, percent: Self) -> Option<Self> {
+        // Dropping trailing zeros of the percentage keeps the scale of the result small.
+        let (mut factor, mut factor_scale) = (percent.value, percent.scale);
+        while factor_scale > 0 && factor % 10 == 0 {
+            factor /= 10;
+            factor_scale -= 1;
+        }
+        let scale = self.scale + factor_scale + 2;
+        if scale > MAX_SCALE {
+            return None;
+        }
+        self.value
+            .checked_mul(factor)
+            .map(|value| Self::with_scale(value, scale))
+    }
+
+    fn upscale(self, scale: u32) -> Option<Self> {
+        self.value
+            .checked_mul(one(scale - self.scale))
+            .map(|value| Self::with_scale(value, scale))
+    }
+
     fn trunc_fract(&self) -> (UnderlyingAmountType, UnderlyingAmountType) {
-        let trunc = self.value / AMOUNT_ONE;
-        let xx = trunc * AMOUNT_ONE;
+        let one = one(self.scale);
+        let trunc = self.value / one;
+        let xx = trunc * one;
         let fract = self.value - xx;
-        debug_assert!(fract < AMOUNT_ONE);
+        debug_assert!(fract < one);
         (trunc, fract)
     }
 }
 
+/// Values of two amounts brought to the larger of their scales.
+fn align(a: Amount, b: Amount) -> Option<(UnderlyingAmountType, UnderlyingAmountType, u32)> {
+    let scale = a.scale.max(b.scale);
+    Some((a.upscale(scale)?.value, b.upscale(scale)?.value, scale))
+}
+
+impl PartialEq for Amount {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Amount {}
+
+impl PartialOrd for Amount {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Amount {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match align(*self, *other) {
+            Some((a, b, _)) => a.cmp(&b),
+            // Only the amount with the smaller scale can overflow when aligned, which makes it
+            // the larger one in magnitude.
+            None if self.scale < other.scale => self.value.cmp(&0),
+            None => 0.cmp(&other.value),
+        }
+    }
+}
+
+/// Panics on overflow, also in release builds. Use [`Amount::checked_add`] for amounts
+/// coming from untrusted input.
 impl Add for Amount {
     type Output = Self;
 
     fn add(self, other: Self) -> Self::Output {
-        Self::new(self.value + other.value)
+        self.checked_add(other).expect("amount overflow")
     }
 }
 
 impl AddAssign for Amount {
     fn add_assign(&mut self, other: Self) {
-        self.value += other.value;
+        *self = *self + other;
     }
 }
 
+/// Panics on overflow, also in release builds. Use [`Amount::checked_sub`] for amounts
+/// coming from untrusted input.
 impl Sub for Amount {
     type Output = Self;
 
     fn sub(self, other: Self) -> Self::Output {
-        Self::new(self.value - other.value)
+        self.checked_sub(other).expect("amount overflow")
     }
 }
 
@@ -90,7 +273,7 @@ impl fmt::Display for Amount {
         } else {
             let fract = fract.abs();
             let (count, fract) = count_remove_trailing_zeroes(fract);
-            let width = DECIMAL_PLACES as usize - count;
+            let width = self.scale as usize - count;
             if self.value < 0 && trunc == 0 {
                 write!(f, "-{}.{:0>width$}", trunc, fract, width = width)
             } else {
@@ -100,19 +283,164 @@ impl fmt::Display for Amount {
     }
 }
 
-fn parse_fractional_str(s: &str) -> UnderlyingAmountType {
-    let mut floating_point_bytes = [b'0'; 6];
-    floating_point_bytes[1] = b'.';
-    floating_point_bytes[2..]
-        .iter_mut()
-        .zip(s.chars())
-        .for_each(|(a, b)| *a = b as u8);
-    let floating_point = f32::from_str(
-        std::str::from_utf8(&floating_point_bytes)
-            .expect("from_utf8 on fractional part buf failed"),
-    )
-    .expect("parsing fractional part failed");
-    (floating_point * AMOUNT_ONE as f32) as UnderlyingAmountType
+/// How to round an amount to fewer decimal places.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// To the nearest value, ties to the even one.
+    #[default]
+    HalfEven,
+    /// To the nearest value, ties away from zero.
+    HalfUp,
+    /// Towards negative infinity.
+    Down,
+    /// Towards zero, dropping the extra decimal places.
+    TowardZero,
+}
+
+/// How the dropped decimal places compare to half a unit of the last place kept.
+#[derive(Clone, Copy)]
+enum Discarded {
+    Zero,
+    BelowHalf,
+    Half,
+    AboveHalf,
+}
+
+impl Discarded {
+    fn from_digits(digits: &str) -> Self {
+        let rest_is_zero = digits.bytes().skip(1).all(|b| b == b'0');
+        match digits.bytes().next() {
+            None => Discarded::Zero,
+            Some(b'0') if rest_is_zero => Discarded::Zero,
+            Some(b'5') if rest_is_zero => Discarded::Half,
+            Some(b) if b < b'5' => Discarded::BelowHalf,
+            Some(_) => Discarded::AboveHalf,
+        }
+    }
+}
+
+impl RoundingMode {
+    /// Rounds `truncated`, a value with the dropped decimal places cut off towards zero.
+    /// Returns `None` if the rounded value does not fit.
+    fn round(
+        self,
+        truncated: UnderlyingAmountType,
+        negative: bool,
+        discarded: Discarded,
+    ) -> Option<UnderlyingAmountType> {
+        let away_from_zero = match (self, discarded) {
+            (_, Discarded::Zero) | (RoundingMode::TowardZero, _) => false,
+            (RoundingMode::Down, _) => negative,
+            (RoundingMode::HalfUp, discarded) => !matches!(discarded, Discarded::BelowHalf),
+            (RoundingMode::HalfEven, Discarded::BelowHalf) => false,
+            (RoundingMode::HalfEven, Discarded::Half) => truncated % 2 != 0,
+            (RoundingMode::HalfEven, Discarded::AboveHalf) => true,
+        };
+        if away_from_zero {
+            truncated.checked_add(if negative { -1 } else { 1 })
+        } else {
+            Some(truncated)
+        }
+    }
+}
+
+impl FromStr for RoundingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "half-even" => Ok(RoundingMode::HalfEven),
+            "half-up" => Ok(RoundingMode::HalfUp),
+            "down" => Ok(RoundingMode::Down),
+            "toward-zero" => Ok(RoundingMode::TowardZero),
+            _ => Err(format!(
+                "invalid rounding mode `{}`, expected `half-even`, `half-up`, `down` or `toward-zero`",
+                s
+            )),
+        }
+    }
+}
+
+/// What to do with input amounts that have more decimal places than their scale.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExcessPrecision {
+    /// Refuse the amount with [`ParseAmountError::TooManyDecimalPlaces`].
+    #[default]
+    Reject,
+    /// Round the amount to its scale.
+    Round(RoundingMode),
+}
+
+impl FromStr for ExcessPrecision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            None if s == "reject" => Ok(ExcessPrecision::Reject),
+            Some(("round", mode)) => mode.parse().map(ExcessPrecision::Round),
+            _ => Err(format!(
+                "invalid excess precision policy `{}`, expected `reject` or `round=<mode>`",
+                s
+            )),
+        }
+    }
+}
+
+/// Error returned when a string cannot be parsed into an [`Amount`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseAmountError {
+    /// The input contained no digits.
+    Empty,
+    /// The input contained a character that is not a digit, a sign or a single dot.
+    InvalidDigit,
+    /// The input has more fractional digits than the given scale.
+    TooManyDecimalPlaces(u32),
+    /// The value does not fit into the underlying integer type.
+    Overflow,
+}
+
+impl fmt::Display for ParseAmountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseAmountError::Empty => write!(f, "amount is empty"),
+            ParseAmountError::InvalidDigit => write!(f, "invalid digit found in amount"),
+            ParseAmountError::TooManyDecimalPlaces(scale) => {
+                write!(f, "amount has more than {} decimal places", scale)
+            }
+            ParseAmountError::Overflow => write!(f, "amount is out of range"),
+        }
+    }
+}
+
+impl std::error::Error for ParseAmountError {}
+
+/// Parses decimal digits into `value`, accumulating towards the sign given by `sign`
+/// so that the most negative value of the underlying type can be represented.
+fn accumulate_digits(
+    mut value: UnderlyingAmountType,
+    digits: &str,
+    sign: UnderlyingAmountType,
+) -> Result<UnderlyingAmountType, ParseAmountError> {
+    for b in digits.bytes() {
+        if !b.is_ascii_digit() {
+            return Err(ParseAmountError::InvalidDigit);
+        }
+        let digit = (b - b'0') as UnderlyingAmountType;
+        value = value
+            .checked_mul(10)
+            .and_then(|v| v.checked_add(sign * digit))
+            .ok_or(ParseAmountError::Overflow)?;
+    }
+    Ok(value)
+}
+
+/// Parses an amount with [`DECIMAL_PLACES`], rejecting further decimal places.
+impl FromStr for Amount {
+    type Err = ParseAmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Amount::parse(s, DECIMAL_PLACES, ExcessPrecision::Reject)
+    }
 }
 
 impl<'de> Deserialize<'de> for Amount {
@@ -120,29 +448,34 @@ impl<'de> Deserialize<'de> for Amount {
     where
         D: Deserializer<'de>,
     {
-        let s: &str = Deserialize::deserialize(deserializer)?;
-        let mut it = s.split('.');
-        if let Some(trunc_str) = it.next() {
-            let trunc = UnderlyingAmountType::from_str_radix(trunc_str, 10)
-                .expect("could not parse whole part of amount");
-            let amount = if let Some(fract_str) = it.next() {
-                let fract = parse_fractional_str(fract_str);
-                Amount::new(trunc * AMOUNT_ONE + fract)
-            } else {
-                Amount::new(trunc)
-            };
-            Ok(amount)
-        } else {
-            Err(serdeError::custom(String::from(
-                "could not deserialize amount",
-            )))
-        }
+        let s: Cow<str> = Deserialize::deserialize(deserializer)?;
+        s.parse().map_err(serdeError::custom)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+
+    const AMOUNT_ONE: UnderlyingAmountType = one(DECIMAL_PLACES);
+
+    /// Largest and smallest amounts of the selected backend, followed by the closest values
+    /// out of range.
+    #[cfg(not(feature = "i128"))]
+    const LIMITS: [&str; 4] = [
+        "922337203685477.5807",
+        "-922337203685477.5808",
+        "922337203685477.5808",
+        "-922337203685477.5809",
+    ];
+    #[cfg(feature = "i128")]
+    const LIMITS: [&str; 4] = [
+        "17014118346046923173168730371588410.5727",
+        "-17014118346046923173168730371588410.5728",
+        "17014118346046923173168730371588410.5728",
+        "-17014118346046923173168730371588410.5729",
+    ];
 
     #[test]
     fn formatting() {
@@ -168,6 +501,18 @@ mod tests {
         assert_eq!(format!("{}", Amount::new(-5000)), "-0.5");
     }
 
+    #[test]
+    fn formatting_limits() {
+        assert_eq!(
+            Amount::new(UnderlyingAmountType::MAX).to_string(),
+            LIMITS[0]
+        );
+        assert_eq!(
+            Amount::new(UnderlyingAmountType::MIN).to_string(),
+            LIMITS[1]
+        );
+    }
+
     #[test]
     fn adding() {
         assert_eq!(Amount::new(11111) + Amount::new(0), Amount::new(11111));
@@ -184,6 +529,29 @@ mod tests {
         assert_eq!(Amount::new(10000) - Amount::new(10001), Amount::new(-1));
     }
 
+    #[test]
+    fn checked_arithmetic() {
+        let max = Amount::new(UnderlyingAmountType::MAX);
+        let min = Amount::new(UnderlyingAmountType::MIN);
+        assert_eq!(
+            max.checked_add(Amount::new(-1)),
+            Some(Amount::new(max.value - 1))
+        );
+        assert_eq!(max.checked_add(Amount::new(1)), None);
+        assert_eq!(
+            min.checked_sub(Amount::new(-1)),
+            Some(Amount::new(min.value + 1))
+        );
+        assert_eq!(min.checked_sub(Amount::new(1)), None);
+        assert_eq!(Amount::new(0).checked_sub(min), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "amount overflow")]
+    fn adding_overflows() {
+        let _ = Amount::new(UnderlyingAmountType::MAX) + Amount::new(1);
+    }
+
     #[test]
     fn counting_and_removing_trailing_zeroes() {
         assert_eq!(count_remove_trailing_zeroes(0), (0, 0));
@@ -196,4 +564,255 @@ mod tests {
         assert_eq!(count_remove_trailing_zeroes(900090), (1, 90009));
         assert_eq!(count_remove_trailing_zeroes(50000000000), (10, 5));
     }
+
+    #[test]
+    fn parsing() {
+        assert_eq!("0".parse(), Ok(Amount::new(0)));
+        assert_eq!("1".parse(), Ok(Amount::new(10000)));
+        assert_eq!("1.0".parse(), Ok(Amount::new(10000)));
+        assert_eq!("1.5".parse(), Ok(Amount::new(15000)));
+        assert_eq!("0.0003".parse(), Ok(Amount::new(3)));
+        assert_eq!("1.9999".parse(), Ok(Amount::new(19999)));
+        assert_eq!(".5".parse(), Ok(Amount::new(5000)));
+        assert_eq!("5.".parse(), Ok(Amount::new(50000)));
+        assert_eq!("+2.25".parse(), Ok(Amount::new(22500)));
+        assert_eq!("-2.25".parse(), Ok(Amount::new(-22500)));
+        assert_eq!("-.5".parse(), Ok(Amount::new(-5000)));
+        assert_eq!("  3.1 \t".parse(), Ok(Amount::new(31000)));
+        assert_eq!("007.0100".parse(), Ok(Amount::new(70100)));
+        assert_eq!(
+            LIMITS[0].parse(),
+            Ok(Amount::new(UnderlyingAmountType::MAX))
+        );
+        assert_eq!(
+            LIMITS[1].parse(),
+            Ok(Amount::new(UnderlyingAmountType::MIN))
+        );
+    }
+
+    #[test]
+    fn parsing_errors() {
+        assert_eq!("".parse::<Amount>(), Err(ParseAmountError::Empty));
+        assert_eq!("   ".parse::<Amount>(), Err(ParseAmountError::Empty));
+        assert_eq!("-".parse::<Amount>(), Err(ParseAmountError::Empty));
+        assert_eq!(".".parse::<Amount>(), Err(ParseAmountError::InvalidDigit));
+        assert_eq!(
+            "1.00001".parse::<Amount>(),
+            Err(ParseAmountError::TooManyDecimalPlaces(4))
+        );
+        assert_eq!(
+            "0.12345".parse::<Amount>(),
+            Err(ParseAmountError::TooManyDecimalPlaces(4))
+        );
+        assert_eq!(
+            "1.2.3".parse::<Amount>(),
+            Err(ParseAmountError::InvalidDigit)
+        );
+        assert_eq!(
+            "1.2.345".parse::<Amount>(),
+            Err(ParseAmountError::InvalidDigit)
+        );
+        assert_eq!("--1".parse::<Amount>(), Err(ParseAmountError::InvalidDigit));
+        assert_eq!("1e5".parse::<Amount>(), Err(ParseAmountError::InvalidDigit));
+        assert_eq!(
+            "1 000".parse::<Amount>(),
+            Err(ParseAmountError::InvalidDigit)
+        );
+        assert_eq!("abc".parse::<Amount>(), Err(ParseAmountError::InvalidDigit));
+        assert_eq!(LIMITS[2].parse::<Amount>(), Err(ParseAmountError::Overflow));
+        assert_eq!(LIMITS[3].parse::<Amount>(), Err(ParseAmountError::Overflow));
+        assert_eq!(
+            "1000000000000000000000000000000000000000".parse::<Amount>(),
+            Err(ParseAmountError::Overflow)
+        );
+    }
+
+    #[test]
+    fn scales() {
+        assert_eq!(Amount::with_scale(150, 2).to_string(), "1.5");
+        assert_eq!(Amount::with_scale(150, 0).to_string(), "150");
+        assert_eq!(Amount::with_scale(-5, 8).to_string(), "-0.00000005");
+        assert_eq!(Amount::with_scale(150, 2), Amount::new(15000));
+        assert!(Amount::with_scale(151, 2) > Amount::new(15000));
+        assert!(Amount::with_scale(-1, 0) < Amount::new(UnderlyingAmountType::MAX));
+
+        let sum = Amount::with_scale(1, 2) + Amount::with_scale(1, 8);
+        assert_eq!(
+            (sum.to_string(), sum.scale()),
+            (String::from("0.01000001"), 8)
+        );
+
+        // Aligning to the larger scale overflows, the smaller scale decides the order.
+        let large = Amount::with_scale(UnderlyingAmountType::MAX, 0);
+        let small = Amount::with_scale(1, MAX_SCALE);
+        assert_eq!(large.cmp(&small), Ordering::Greater);
+        assert_eq!(small.cmp(&large), Ordering::Less);
+        assert_eq!(large.checked_add(small), None);
+    }
+
+    #[test]
+    fn rescaling() {
+        let cases = [
+            // value, half-even, half-up, down, toward zero
+            ("1.25", ["1.2", "1.3", "1.2", "1.2"]),
+            ("1.35", ["1.4", "1.4", "1.3", "1.3"]),
+            ("1.26", ["1.3", "1.3", "1.2", "1.2"]),
+            ("1.24", ["1.2", "1.2", "1.2", "1.2"]),
+            ("-1.25", ["-1.2", "-1.3", "-1.3", "-1.2"]),
+            ("-1.26", ["-1.3", "-1.3", "-1.3", "-1.2"]),
+            ("-1.2", ["-1.2", "-1.2", "-1.2", "-1.2"]),
+            ("-0.05", ["0", "-0.1", "-0.1", "0"]),
+        ];
+        let modes = [
+            RoundingMode::HalfEven,
+            RoundingMode::HalfUp,
+            RoundingMode::Down,
+            RoundingMode::TowardZero,
+        ];
+        for (input, expected) in cases {
+            for (mode, expected) in modes.into_iter().zip(expected) {
+                let amount = input.parse::<Amount>().unwrap();
+                let rescaled = amount.rescale(1, mode).unwrap();
+                assert_eq!(rescaled.to_string(), expected, "{} {:?}", input, mode);
+                assert_eq!(rescaled.scale(), 1);
+
+                let excess = ExcessPrecision::Round(mode);
+                let parsed = Amount::parse(&format!("{}000", input), 1, excess).unwrap();
+                assert_eq!(parsed, rescaled, "{} {:?}", input, mode);
+            }
+        }
+        assert_eq!(
+            Amount::new(15000).rescale(8, RoundingMode::Down),
+            Some(Amount::with_scale(150000000, 8))
+        );
+        assert_eq!(
+            Amount::new(UnderlyingAmountType::MAX).rescale(MAX_SCALE, RoundingMode::Down),
+            None
+        );
+    }
+
+    #[test]
+    fn percentages() {
+        let amount = Amount::new(1234500);
+        assert_eq!(
+            amount.checked_percent(Amount::new(15000)),
+            Some(Amount::with_scale(185175, 5))
+        );
+        assert_eq!(
+            amount
+                .checked_percent(Amount::new(15000))
+                .map(|p| p.scale()),
+            Some(7)
+        );
+        assert_eq!(
+            Amount::with_scale(100, 0).checked_percent(Amount::new(-2500)),
+            Some(Amount::with_scale(-25, 2))
+        );
+        assert_eq!(
+            Amount::new(UnderlyingAmountType::MAX).checked_percent(Amount::new(20000)),
+            None
+        );
+        assert_eq!(
+            Amount::with_scale(1, MAX_SCALE).checked_percent(Amount::new(10000)),
+            None
+        );
+    }
+
+    #[test]
+    fn parsing_with_scale() {
+        let reject = ExcessPrecision::Reject;
+        assert_eq!(
+            Amount::parse("100", 0, reject),
+            Ok(Amount::with_scale(100, 0))
+        );
+        assert_eq!(
+            Amount::parse("1.5", 0, reject),
+            Err(ParseAmountError::TooManyDecimalPlaces(0))
+        );
+        assert_eq!(
+            Amount::parse("0.12345678", 8, reject),
+            Ok(Amount::with_scale(12345678, 8))
+        );
+        assert_eq!(
+            Amount::parse("1.005", 2, ExcessPrecision::Round(RoundingMode::HalfEven)),
+            Ok(Amount::with_scale(100, 2))
+        );
+        assert_eq!(
+            Amount::parse(
+                "1.00500001",
+                2,
+                ExcessPrecision::Round(RoundingMode::HalfEven)
+            ),
+            Ok(Amount::with_scale(101, 2))
+        );
+        assert_eq!(
+            Amount::parse("1.00x", 2, ExcessPrecision::Round(RoundingMode::HalfEven)),
+            Err(ParseAmountError::InvalidDigit)
+        );
+        assert_eq!(
+            Amount::parse(
+                &format!("{}9", LIMITS[0]),
+                DECIMAL_PLACES,
+                ExcessPrecision::Round(RoundingMode::HalfUp)
+            ),
+            Err(ParseAmountError::Overflow)
+        );
+    }
+
+    #[test]
+    fn parsing_policies() {
+        assert_eq!("reject".parse(), Ok(ExcessPrecision::Reject));
+        assert_eq!(
+            "round=half-up".parse(),
+            Ok(ExcessPrecision::Round(RoundingMode::HalfUp))
+        );
+        assert_eq!(
+            "round=toward-zero".parse(),
+            Ok(ExcessPrecision::Round(RoundingMode::TowardZero))
+        );
+        assert!("round".parse::<ExcessPrecision>().is_err());
+        assert!("round=up".parse::<ExcessPrecision>().is_err());
+    }
+
+    #[test]
+    fn deserializing() {
+        // Borrowed, escaped and owned input.
+        assert_eq!(
+            serde_json::from_str::<Amount>(r#""1.5""#).unwrap(),
+            Amount::new(15000)
+        );
+        assert_eq!(
+            serde_json::from_str::<Amount>(r#""\u0031.5""#).unwrap(),
+            Amount::new(15000)
+        );
+        assert_eq!(
+            serde_json::from_reader::<_, Amount>(&br#""-0.25""#[..]).unwrap(),
+            Amount::new(-2500)
+        );
+        assert!(serde_json::from_str::<Amount>(r#""1.2.3""#).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn display_parse_round_trip(value in any::<UnderlyingAmountType>()) {
+            let amount = Amount::new(value);
+            prop_assert_eq!(amount.to_string().parse::<Amount>(), Ok(amount));
+        }
+
+        #[test]
+        fn parse_display_round_trip(
+            negative in any::<bool>(),
+            // Leaves room for the fraction, the largest integer part overflows with some.
+            trunc in 0..(UnderlyingAmountType::MAX / AMOUNT_ONE),
+            fract in "[0-9]{0,4}",
+        ) {
+            let s = format!("{}{}.{}", if negative { "-" } else { "" }, trunc, fract);
+            let amount = s.parse::<Amount>().unwrap();
+            prop_assert_eq!(amount.to_string().parse::<Amount>(), Ok(amount));
+
+            let padded = format!("{:0<4}", fract).parse::<UnderlyingAmountType>().unwrap();
+            let expected = trunc * AMOUNT_ONE + padded;
+            prop_assert_eq!(amount, Amount::new(if negative { -expected } else { expected }));
+        }
+    }
 }
@@ -0,0 +1,437 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use rust_challenge_csv::amount::MAX_SCALE;
+use rust_challenge_csv::input::{ReadOptions, ReadSummary};
+use rust_challenge_csv::{Config, Error, ErrorKind, SortOrder};
+
+pub const HELP: &str = "\
+Applies transactions to client accounts and writes the resulting balances.
+
+Usage: rust-challenge-csv [COMMAND] [OPTIONS] <INPUT>...
+
+Commands:
+  process   Apply the transactions and write the balances of all clients (default)
+  validate  Parse and apply the transactions without writing balances, failing on
+            invalid rows
+  stats     Write the number of transactions per type and of rejected ones
+
+Arguments:
+  <INPUT>...  Files to read one after the other, `-` for stdin at most once
+
+Options:
+  -o, --output <PATH>            Write to PATH instead of stdout
+  -i, --input-format <FORMAT>    Format of the inputs: `csv` (default) or `jsonl` with an
+                                 object per line, whose amounts may be strings or numbers
+  -f, --format <FORMAT>          Format of the balances written by `process`: `csv`
+                                 (default) or `jsonl`
+  -s, --sort <KEYS>              Order of the balances, by client and currency by default.
+                                 KEYS are comma separated fields out of `client`,
+                                 `currency`, `available`, `held`, `total` and `locked`,
+                                 each optionally followed by `:asc` or `:desc`, as in
+                                 `locked:desc,total:desc`. Ties are ordered by client
+      --with-currency            Add a `currency` column after the client to CSV balances,
+                                 required once a balance is in another currency than the
+                                 default one
+  -d, --delimiter <CHAR>         Field delimiter of CSV input and output, `,` by
+                                 default and `tab` for tabs
+      --on-error <POLICY>        What to do with invalid rows: `abort` (default), `skip`
+                                 or `quarantine=<PATH>` to also copy them to PATH
+      --merge <MODE>             How to combine several inputs: `concatenate` (default)
+                                 or `sequence` to interleave them by their `seq` column
+      --on-duplicate <POLICY>    What to do with reused transaction ids: `reject`
+                                 (default) or `deduplicate` to drop exact repeats
+      --currency <CODE>          Currency of rows without one, `USD` by default
+      --scale [<CODE>=]<N>       Decimal places of amounts, 4 by default, or of the
+                                 currency CODE only
+      --excess-precision <MODE>  What to do with amounts with more decimal places:
+                                 `reject` (default) or `round=<half-even|half-up|down|toward-zero>`
+      --log-level <LEVEL>        Diagnostics written to stderr: `off`, `error`, `warn`
+                                 (default) or `info`
+  -h, --help                     Print this help
+
+Exit codes:
+  0  All rows were valid and took effect
+  2  The command line is invalid, or lacks `--with-currency` for balances in another
+     currency than the default
+  3  Reading the input or writing the output failed
+  4  An invalid row stopped the run, or `validate` found invalid rows
+  5  The output was written, but rows were skipped as invalid or transactions were
+     rejected; for `validate`, which writes nothing, all rows were valid but
+     transactions were rejected
+";
+
+/// Outcome of a run, reported as the exit code of the process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Success = 0,
+    Usage = 2,
+    Io = 3,
+    InvalidInput = 4,
+    Partial = 5,
+}
+
+impl Status {
+    /// Status of a run stopped by `err`.
+    pub fn of_error(err: &Error) -> Self {
+        match err.kind() {
+            ErrorKind::Io(_) => Status::Io,
+            ErrorKind::Csv(err) if err.is_io_error() => Status::Io,
+            _ => Status::InvalidInput,
+        }
+    }
+
+    /// Status of a run of `command` that read all of its input.
+    ///
+    /// `validate` fails on invalid rows only, rejected transactions of valid rows are
+    /// reported as [`Partial`](Status::Partial) like for the other commands.
+    pub fn of_summary(command: Command, summary: &ReadSummary) -> Self {
+        if command == Command::Validate && summary.invalid > 0 {
+            Status::InvalidInput
+        } else if summary.invalid > 0 || summary.rejected_total() > 0 {
+            Status::Partial
+        } else {
+            Status::Success
+        }
+    }
+}
+
+/// What the binary does with its inputs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Writes the balances of all clients.
+    Process,
+    /// Only checks that all rows are valid.
+    Validate,
+    /// Writes the number of transactions per type and of rejected ones.
+    Stats,
+}
+
+/// Format the balances are written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    Jsonl,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            _ => Err(format!(
+                "invalid output format `{}`, expected `csv` or `jsonl`",
+                s
+            )),
+        }
+    }
+}
+
+/// Which diagnostics are written to stderr, each level including the ones before it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Off,
+    /// Errors that stop the run.
+    Error,
+    /// Rows that were invalid or rejected, and accounts with negative funds.
+    #[default]
+    Warn,
+    /// A summary of what was read.
+    Info,
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(LogLevel::Off),
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            _ => Err(format!(
+                "invalid log level `{}`, expected `off`, `error`, `warn` or `info`",
+                s
+            )),
+        }
+    }
+}
+
+/// Arguments of a run.
+pub struct Args {
+    pub command: Command,
+    /// Paths of the inputs, `-` standing for stdin.
+    pub inputs: Vec<String>,
+    /// Path of the output, stdout if missing.
+    pub output: Option<PathBuf>,
+    pub format: OutputFormat,
+    pub order: SortOrder,
+    /// Whether CSV balances have a `currency` column.
+    pub with_currency: bool,
+    pub log_level: LogLevel,
+    pub read: ReadOptions,
+    pub config: Config,
+}
+
+/// Outcome of parsing the command line.
+pub enum Parsed {
+    /// `--help` was given.
+    Help,
+    Run(Box<Args>),
+}
+
+/// Parses the command line, without the name of the binary. Options take their value
+/// either as the next argument or after an `=`, as in `--format=jsonl`.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Parsed, String> {
+    let mut args = args.into_iter().peekable();
+    let command = match args.peek().map(String::as_str) {
+        Some("process") => Some(Command::Process),
+        Some("validate") => Some(Command::Validate),
+        Some("stats") => Some(Command::Stats),
+        _ => None,
+    };
+    if command.is_some() {
+        args.next();
+    }
+    let mut parsed = Args {
+        command: command.unwrap_or(Command::Process),
+        inputs: Vec::new(),
+        output: None,
+        format: OutputFormat::default(),
+        order: SortOrder::default(),
+        with_currency: false,
+        log_level: LogLevel::default(),
+        read: ReadOptions::default(),
+        config: Config::default(),
+    };
+
+    let mut format_given = false;
+    while let Some(arg) = args.next() {
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_owned())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("missing value for `{}`", name))
+        };
+        match name {
+            "-h" | "--help" => return Ok(Parsed::Help),
+            "-o" | "--output" => parsed.output = Some(PathBuf::from(value()?)),
+            "-f" | "--format" => {
+                parsed.format = parse_value(&value()?)?;
+                format_given = true;
+            }
+            "-s" | "--sort" => parsed.order = parse_value(&value()?)?,
+            "--with-currency" if inline.is_none() => parsed.with_currency = true,
+            "--with-currency" => return Err(format!("`{}` takes no value", name)),
+            "-d" | "--delimiter" => parsed.read.delimiter = parse_delimiter(&value()?)?,
+            "--on-error" => parsed.read.policy = parse_value(&value()?)?,
+            "--merge" => parsed.read.merge = parse_value(&value()?)?,
+            "-i" | "--input-format" => parsed.read.format = parse_value(&value()?)?,
+            "--on-duplicate" => parsed.config.duplicates = parse_value(&value()?)?,
+            "--currency" => parsed.config.currency = parse_value(&value()?)?,
+            "--scale" => {
+                let value = value()?;
+                let precision = &mut parsed.config.precision;
+                match value.split_once('=') {
+                    Some((currency, scale)) => {
+                        let scale = parse_scale(scale)?;
+                        precision.scales.insert(parse_value(currency)?, scale);
+                    }
+                    None => precision.scale = parse_scale(&value)?,
+                }
+            }
+            "--excess-precision" => parsed.config.precision.excess = parse_value(&value()?)?,
+            "--log-level" => parsed.log_level = parse_value(&value()?)?,
+            // Stdin can only be read once, and locking it twice would never return.
+            "-" if parsed.inputs.iter().any(|input| input == "-") => {
+                return Err(String::from("`-` for stdin given more than once"))
+            }
+            "-" => parsed.inputs.push(arg),
+            _ if name.starts_with('-') => return Err(format!("unknown option `{}`", name)),
+            _ => parsed.inputs.push(arg),
+        }
+    }
+    if parsed.inputs.is_empty() {
+        return Err(String::from("no input given"));
+    }
+    if format_given && parsed.command != Command::Process {
+        return Err(String::from("`--format` only applies to `process`"));
+    }
+    Ok(Parsed::Run(Box::new(parsed)))
+}
+
+fn parse_value<T: FromStr>(value: &str) -> Result<T, String>
+where
+    T::Err: fmt::Display,
+{
+    value.parse().map_err(|err: T::Err| err.to_string())
+}
+
+fn parse_scale(value: &str) -> Result<u32, String> {
+    let scale = parse_value(value)?;
+    if scale > MAX_SCALE {
+        return Err(format!("scale must be at most {}", MAX_SCALE));
+    }
+    Ok(scale)
+}
+
+fn parse_delimiter(value: &str) -> Result<u8, String> {
+    match value.as_bytes() {
+        b"tab" | b"\\t" => Ok(b'\t'),
+        &[delimiter] if delimiter.is_ascii() && delimiter != b'"' && delimiter != b'\n' => {
+            Ok(delimiter)
+        }
+        _ => Err(format!(
+            "invalid delimiter `{}`, expected a single character or `tab`",
+            value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_challenge_csv::config::DuplicatePolicy;
+    use rust_challenge_csv::input::{ErrorPolicy, InputFormat, Merge};
+    use rust_challenge_csv::RejectReason;
+
+    fn parse_args(args: &str) -> Result<Args, String> {
+        match parse(args.split_whitespace().map(String::from))? {
+            Parsed::Help => Err(String::from("help")),
+            Parsed::Run(args) => Ok(*args),
+        }
+    }
+
+    #[test]
+    fn defaults() {
+        let args = parse_args("transactions.csv").unwrap();
+        assert_eq!(args.command, Command::Process);
+        assert_eq!(args.inputs, ["transactions.csv"]);
+        assert_eq!(args.output, None);
+        assert_eq!(args.format, OutputFormat::Csv);
+        assert_eq!(args.order, SortOrder::default());
+        assert!(!args.with_currency);
+        assert_eq!(args.log_level, LogLevel::Warn);
+        assert_eq!(args.read.format, InputFormat::Csv);
+        assert_eq!(args.read.delimiter, b',');
+        assert!(matches!(args.read.policy, ErrorPolicy::Abort));
+    }
+
+    #[test]
+    fn commands_and_options() {
+        let args = parse_args(
+            "stats a.csv - --merge sequence -o out.txt -d tab \
+             --on-error quarantine=bad.csv --on-duplicate deduplicate --scale JPY=0 \
+             --log-level info --sort locked:desc --with-currency",
+        )
+        .unwrap();
+        assert_eq!(args.command, Command::Stats);
+        assert_eq!(args.inputs, ["a.csv", "-"]);
+        assert_eq!(args.output, Some(PathBuf::from("out.txt")));
+        assert_eq!(args.read.delimiter, b'\t');
+        assert_eq!(args.read.merge, Merge::Sequence);
+        assert!(matches!(args.read.policy, ErrorPolicy::Quarantine(_)));
+        assert_eq!(args.config.duplicates, DuplicatePolicy::Deduplicate);
+        assert_eq!(
+            args.config.precision.scales.get(&"JPY".parse().unwrap()),
+            Some(&0)
+        );
+        assert_eq!(args.log_level, LogLevel::Info);
+        assert_eq!(args.order, "locked:desc".parse().unwrap());
+        assert!(args.with_currency);
+
+        assert_eq!(
+            parse_args("validate -d ; a.csv").unwrap().read.delimiter,
+            b';'
+        );
+        assert_eq!(
+            parse_args("process a.csv --format=jsonl").unwrap().format,
+            OutputFormat::Jsonl
+        );
+        assert_eq!(
+            parse_args("a.jsonl --input-format jsonl")
+                .unwrap()
+                .read
+                .format,
+            InputFormat::Jsonl
+        );
+        assert!(matches!(
+            parse(vec![String::from("a.csv"), String::from("--help")]),
+            Ok(Parsed::Help)
+        ));
+    }
+
+    #[test]
+    fn statuses() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        assert_eq!(Status::of_error(&Error::from(io)), Status::Io);
+        let err = Error::new(ErrorKind::MissingAmount).with_row(2);
+        assert_eq!(Status::of_error(&err), Status::InvalidInput);
+
+        let mut summary = ReadSummary {
+            rows: 2,
+            ..ReadSummary::default()
+        };
+        assert_eq!(
+            Status::of_summary(Command::Process, &summary),
+            Status::Success
+        );
+        summary.invalid = 1;
+        assert_eq!(
+            Status::of_summary(Command::Process, &summary),
+            Status::Partial
+        );
+        assert_eq!(
+            Status::of_summary(Command::Validate, &summary),
+            Status::InvalidInput
+        );
+        summary.invalid = 0;
+        summary.rejected.insert(RejectReason::InsufficientFunds, 1);
+        assert_eq!(
+            Status::of_summary(Command::Validate, &summary),
+            Status::Partial
+        );
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let err = |args| parse_args(args).err().unwrap();
+        assert_eq!(err(""), "no input given");
+        assert_eq!(err("process"), "no input given");
+        assert_eq!(err("a.csv --output"), "missing value for `--output`");
+        assert_eq!(err("a.csv --verbose"), "unknown option `--verbose`");
+        assert_eq!(err("- a.csv -"), "`-` for stdin given more than once");
+        assert_eq!(
+            err("stats a.csv -f jsonl"),
+            "`--format` only applies to `process`"
+        );
+        assert_eq!(
+            err("a.csv --with-currency=yes"),
+            "`--with-currency` takes no value"
+        );
+        assert_eq!(
+            err("a.csv --scale 40"),
+            format!("scale must be at most {}", MAX_SCALE)
+        );
+        assert_eq!(
+            err("a.csv -f xml"),
+            "invalid output format `xml`, expected `csv` or `jsonl`"
+        );
+        assert_eq!(
+            err("a.csv -i xml"),
+            "invalid input format `xml`, expected `csv` or `jsonl`"
+        );
+        assert_eq!(
+            err("a.csv -d ab"),
+            "invalid delimiter `ab`, expected a single character or `tab`"
+        );
+    }
+}
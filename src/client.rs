@@ -1,123 +1,627 @@
-use std::{collections::HashMap, fmt};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
 
-use crate::{Amount, ClientId, TransactionId};
+use crate::config::{LockPolicy, OverdraftMode};
+use crate::dispute::BalanceChange;
+use crate::ledger::{Account, JournalEntry, Ledger, Posting};
+use crate::{
+    error::ErrorKind, Amount, ClientId, Config, Currency, Outcome, RejectReason, TransactionId,
+    TransactionState,
+};
 
+/// Account of a single client. Its funds are kept in the [`Ledger`] it is given.
 pub struct Client {
     id: ClientId,
-    transactions: Vec<ClientTransaction>,
+    /// Every currency the client holds or held funds in.
+    currencies: BTreeSet<Currency>,
+    locked: bool,
+    /// Deposits and withdrawals that took effect.
+    transactions: HashMap<TransactionId, AppliedTransaction>,
+}
+
+/// Funds of a client in a single currency.
+#[derive(Clone, Copy)]
+struct Balance {
+    available: Amount,
+    held: Amount,
+}
+
+/// A deposit or withdrawal that took effect, along with its dispute state.
+struct AppliedTransaction {
+    is_withdrawal: bool,
+    amount: Amount,
+    currency: Currency,
+    /// Part of the amount under open disputes.
+    disputed: Amount,
+    /// Part of `disputed` of a deposit that could not be held because of the overdraft limit.
+    unheld: Amount,
+    /// Part of the amount that was charged back.
+    charged_back: Amount,
+    /// Part of the amount that was never disputed.
+    fresh: Amount,
+    state: TransactionState,
+    /// Number of times the most often disputed funds of the transaction were disputed.
+    disputes: u32,
+}
+
+impl AppliedTransaction {
+    /// Part of the amount that can still be disputed.
+    fn undisputed(&self) -> Amount {
+        self.amount - self.disputed - self.charged_back
+    }
+
+    /// System account funds come from or go to when disputes change the client's total
+    /// funds.
+    fn counterpart(&self) -> Account {
+        if self.is_withdrawal {
+            Account::ChargebackLoss
+        } else {
+            Account::Cash
+        }
+    }
+
+    /// Splits `amount` of the disputed part into the held part and the part that could not
+    /// be held. Held funds are released or charged back first.
+    fn split_disputed(&self, amount: Amount) -> (Amount, Amount) {
+        let held = amount.min(self.disputed - self.unheld);
+        (held, amount - held)
+    }
 }
 
 impl Client {
     pub fn new(id: ClientId) -> Self {
         Self {
             id,
-            transactions: Vec::new(),
+            currencies: BTreeSet::new(),
+            locked: false,
+            transactions: HashMap::new(),
         }
     }
 
-    pub fn add_transaction(&mut self, transaction: ClientTransaction) {
+    /// Applies a transaction, posting the changes to the client's funds to `ledger`.
+    pub fn add_transaction(
+        &mut self,
+        transaction: ClientTransaction,
+        config: &Config,
+        ledger: &mut Ledger,
+    ) -> Result<Outcome, ErrorKind> {
+        if self.locked && !allowed_when_locked(&config.lock, transaction.tx_type) {
+            return Ok(Outcome::Rejected(RejectReason::AccountLocked));
+        }
         match transaction.tx_type {
-            ClientTransactionType::Deposit | ClientTransactionType::Withdrawal => {
-                if transaction.amount.unwrap() != Amount::new(0) {
-                    self.transactions.push(transaction);
-                }
+            ClientTransactionType::Deposit => {
+                let amount = transaction.amount.ok_or(ErrorKind::MissingAmount)?;
+                self.deposit(ledger, transaction.id, amount, transaction.currency)
+            }
+            ClientTransactionType::Withdrawal => {
+                let amount = transaction.amount.ok_or(ErrorKind::MissingAmount)?;
+                self.withdraw(
+                    ledger,
+                    transaction.id,
+                    amount,
+                    &transaction.fees,
+                    transaction.currency,
+                )
             }
             ClientTransactionType::Dispute => {
-                if self.transactions.iter().find(|other_tx| {
-                    other_tx.id == transaction.id
-                        && (matches!(other_tx.tx_type, ClientTransactionType::Deposit)
-                            || matches!(other_tx.tx_type, ClientTransactionType::Withdrawal))
-                }).is_some() {
-                    self.transactions.push(transaction);
-                }
+                self.dispute(ledger, transaction.id, transaction.amount, config)
             }
-            ClientTransactionType::Resolve | ClientTransactionType::Chargeback => {
-                if self.transactions.iter().find(|other_tx| {
-                    other_tx.id == transaction.id
-                        && matches!(other_tx.tx_type, ClientTransactionType::Dispute)
-                }).is_some() {
-                    self.transactions.push(transaction);
-                }
+            ClientTransactionType::Resolve => {
+                self.resolve(ledger, transaction.id, transaction.amount, config)
+            }
+            ClientTransactionType::Chargeback => self.chargeback(
+                ledger,
+                transaction.id,
+                transaction.amount,
+                &transaction.fees,
+                config,
+            ),
+            ClientTransactionType::Unlock => Ok(self.unlock()),
+            ClientTransactionType::TransferOut => {
+                let amount = transaction.amount.ok_or(ErrorKind::MissingAmount)?;
+                Ok(self.transfer_out(ledger, amount, transaction.currency))
+            }
+            ClientTransactionType::TransferIn { from } => {
+                let amount = transaction.amount.ok_or(ErrorKind::MissingAmount)?;
+                self.apply(
+                    ledger,
+                    transaction.id,
+                    transaction.currency,
+                    BalanceChange::new(amount, Amount::new(0)),
+                    Account::Available(from),
+                    &[],
+                )?;
+                Ok(Outcome::Applied)
             }
         }
     }
 
-    pub fn get_entry(&self) -> ClientEntry {
-        let mut available = Amount::new(0);
-        let mut held = Amount::new(0);
-        let mut locked = false;
+    fn deposit(
+        &mut self,
+        ledger: &mut Ledger,
+        id: TransactionId,
+        amount: Amount,
+        currency: Currency,
+    ) -> Result<Outcome, ErrorKind> {
+        if amount == Amount::new(0) {
+            return Ok(Outcome::Rejected(RejectReason::ZeroAmount));
+        }
+        if amount < Amount::new(0) {
+            return Ok(Outcome::Rejected(RejectReason::NegativeAmount));
+        }
+        if self.transactions.contains_key(&id) {
+            return Ok(Outcome::Rejected(RejectReason::DuplicateTxId));
+        }
+        self.apply(
+            ledger,
+            id,
+            currency,
+            BalanceChange::new(amount, Amount::new(0)),
+            Account::Cash,
+            &[],
+        )?;
+        self.insert_applied(id, false, amount, currency);
+        Ok(Outcome::Applied)
+    }
 
-        let mut disputed: HashMap<TransactionId, Amount> = HashMap::new();
-        let mut valid_transactions= Vec::new();
+    /// Debits a withdrawal together with its `fees`, all of which must be covered by the
+    /// available funds.
+    fn withdraw(
+        &mut self,
+        ledger: &mut Ledger,
+        id: TransactionId,
+        amount: Amount,
+        fees: &[Amount],
+        currency: Currency,
+    ) -> Result<Outcome, ErrorKind> {
+        if amount == Amount::new(0) {
+            return Ok(Outcome::Rejected(RejectReason::ZeroAmount));
+        }
+        if amount < Amount::new(0) {
+            return Ok(Outcome::Rejected(RejectReason::NegativeAmount));
+        }
+        if self.transactions.contains_key(&id) {
+            return Ok(Outcome::Rejected(RejectReason::DuplicateTxId));
+        }
+        let debit = fees
+            .iter()
+            .try_fold(amount, |debit, fee| debit.checked_add(*fee))
+            .ok_or(ErrorKind::Overflow)?;
+        if self.balance(ledger, currency).available < debit {
+            return Ok(Outcome::Rejected(RejectReason::InsufficientFunds));
+        }
+        self.apply(
+            ledger,
+            id,
+            currency,
+            BalanceChange::new(negate(amount)?, Amount::new(0)),
+            Account::Cash,
+            fees,
+        )?;
+        self.insert_applied(id, true, amount, currency);
+        Ok(Outcome::Applied)
+    }
 
-        for tx in &self.transactions {
-            match tx.tx_type {
-                ClientTransactionType::Deposit => {
-                    let amount = tx.amount.unwrap();
-                    available += amount;
-                    valid_transactions.push(tx);
-                }
-                ClientTransactionType::Withdrawal => {
-                    let amount = tx.amount.unwrap();
-                    if available >= amount && !locked {
-                        available -= amount;
-                        valid_transactions.push(tx);
-                    }
-                }
-                ClientTransactionType::Dispute => {
-                    if let Some(tx_found) = valid_transactions.iter().find(|other_tx| other_tx.id == tx.id) {
-                        let mut amount = tx_found.amount.unwrap();
-                        if matches!(tx_found.tx_type, ClientTransactionType::Withdrawal) {
-                            amount = Amount::new(0) - amount;
+    /// Disputes `amount` of a transaction, or all of the part not yet disputed.
+    fn dispute(
+        &mut self,
+        ledger: &mut Ledger,
+        id: TransactionId,
+        amount: Option<Amount>,
+        config: &Config,
+    ) -> Result<Outcome, ErrorKind> {
+        let applied = match self.transactions.get(&id) {
+            Some(applied) => applied,
+            None => return Ok(Outcome::Rejected(RejectReason::UnknownTx)),
+        };
+        let undisputed = applied.undisputed();
+        if undisputed == Amount::new(0) {
+            return Ok(Outcome::Rejected(match applied.state {
+                TransactionState::Disputed => RejectReason::AlreadyDisputed,
+                _ => RejectReason::AlreadyChargedBack,
+            }));
+        }
+        let amount = match requested(amount, undisputed) {
+            Ok(amount) => amount,
+            Err(reason) => return Ok(Outcome::Rejected(reason)),
+        };
+        // Funds never disputed are taken first, only disputing funds again is up to the
+        // redispute policy.
+        let redisputed = amount - amount.min(applied.fresh);
+        if redisputed > Amount::new(0) && !config.redisputes.allows(applied.disputes) {
+            return Ok(Outcome::Rejected(RejectReason::RedisputeNotAllowed));
+        }
+
+        let mut outcome = Outcome::Applied;
+        let mut unheld = Amount::new(0);
+        let change = if applied.is_withdrawal {
+            match config.dispute_policy.dispute_withdrawal(amount) {
+                Ok(change) => change,
+                Err(reason) => return Ok(Outcome::Rejected(reason)),
+            }
+        } else {
+            // Hold as much as the overdraft limit allows, the rest stays in available funds
+            // and is only taken when the dispute is charged back.
+            let mut held = amount;
+            if let Some(limit) = config.overdraft.limit_for(self.id) {
+                let holdable = self
+                    .balance(ledger, applied.currency)
+                    .available
+                    .checked_add(limit)
+                    .ok_or(ErrorKind::Overflow)?
+                    .max(Amount::new(0));
+                if holdable < held {
+                    match config.overdraft.mode {
+                        OverdraftMode::Refuse => {
+                            return Ok(Outcome::Rejected(RejectReason::OverdraftLimit))
+                        }
+                        OverdraftMode::PartialHold => {
+                            held = holdable;
+                            outcome = Outcome::PartiallyHeld(held);
                         }
-                        disputed.insert(tx.id, amount);
-                        available -= amount;
-                        held += amount;
-                    }
-                }
-                ClientTransactionType::Resolve => {
-                    if let Some(amount) = disputed.get(&tx.id) {
-                        held -= *amount;
-                        available += *amount;
-                        disputed.remove(&tx.id);
-                        let tx_to_remove = tx.id;
-                        valid_transactions.retain(|tx| tx.id != tx_to_remove);
-                    }
-                }
-                ClientTransactionType::Chargeback => {
-                    if let Some(amount) = disputed.get(&tx.id) {
-                        locked = true;
-                        held -= *amount;
-                        disputed.remove(&tx.id);
-                        let tx_to_remove = tx.id;
-                        valid_transactions.retain(|tx| tx.id != tx_to_remove);
                     }
                 }
             }
+            unheld = amount - held;
+            BalanceChange::new(negate(held)?, held)
+        };
+        let (currency, counterpart) = (applied.currency, applied.counterpart());
+        self.apply(ledger, id, currency, change, counterpart, &[])?;
+        let applied = self.transactions.get_mut(&id).expect("checked above");
+        applied.state = TransactionState::Disputed;
+        if redisputed > Amount::new(0) || applied.disputes == 0 {
+            applied.disputes += 1;
+        }
+        applied.fresh -= amount - redisputed;
+        applied.disputed += amount;
+        applied.unheld += unheld;
+        Ok(outcome)
+    }
+
+    /// Resolves `amount` of the disputed part of a transaction, or all of it.
+    fn resolve(
+        &mut self,
+        ledger: &mut Ledger,
+        id: TransactionId,
+        amount: Option<Amount>,
+        config: &Config,
+    ) -> Result<Outcome, ErrorKind> {
+        let (applied, amount) = match self.disputed(id, amount) {
+            Ok(disputed) => disputed,
+            Err(reason) => return Ok(Outcome::Rejected(reason)),
+        };
+        let (held, unheld) = applied.split_disputed(amount);
+        let change = if applied.is_withdrawal {
+            config.dispute_policy.resolve_withdrawal(amount)
+        } else {
+            BalanceChange::new(held, negate(held)?)
+        };
+        let (currency, counterpart) = (applied.currency, applied.counterpart());
+        self.apply(ledger, id, currency, change, counterpart, &[])?;
+        self.settle_dispute(id, amount, unheld, false);
+        Ok(Outcome::Applied)
+    }
+
+    /// Charges back `amount` of the disputed part of a transaction, or all of it, and debits
+    /// `fees` from the available funds, negative ones for fees that are refunded.
+    fn chargeback(
+        &mut self,
+        ledger: &mut Ledger,
+        id: TransactionId,
+        amount: Option<Amount>,
+        fees: &[Amount],
+        config: &Config,
+    ) -> Result<Outcome, ErrorKind> {
+        let (applied, amount) = match self.disputed(id, amount) {
+            Ok(disputed) => disputed,
+            Err(reason) => return Ok(Outcome::Rejected(reason)),
+        };
+        let (held, unheld) = applied.split_disputed(amount);
+        let change = if applied.is_withdrawal {
+            config.dispute_policy.chargeback_withdrawal(amount)
+        } else {
+            BalanceChange::new(negate(unheld)?, negate(held)?)
+        };
+        let (currency, counterpart) = (applied.currency, applied.counterpart());
+        self.apply(ledger, id, currency, change, counterpart, fees)?;
+        self.settle_dispute(id, amount, unheld, true);
+        self.locked = true;
+        Ok(Outcome::Applied)
+    }
+
+    /// Checks that the sending side of a transfer can be debited. Transfers are posted as a
+    /// whole when the receiving side is credited, and unlike withdrawals they are not kept
+    /// since they cannot be disputed.
+    fn transfer_out(&self, ledger: &Ledger, amount: Amount, currency: Currency) -> Outcome {
+        if amount == Amount::new(0) {
+            return Outcome::Rejected(RejectReason::ZeroAmount);
         }
+        if amount < Amount::new(0) {
+            return Outcome::Rejected(RejectReason::NegativeAmount);
+        }
+        if self.balance(ledger, currency).available < amount {
+            return Outcome::Rejected(RejectReason::InsufficientFunds);
+        }
+        Outcome::Applied
+    }
 
-        ClientEntry::new(self.id, available, held, locked)
+    fn unlock(&mut self) -> Outcome {
+        if !self.locked {
+            return Outcome::Rejected(RejectReason::NotLocked);
+        }
+        self.locked = false;
+        Outcome::Applied
+    }
+
+    fn balance(&self, ledger: &Ledger, currency: Currency) -> Balance {
+        Balance {
+            available: ledger.balance(Account::Available(self.id), currency),
+            held: ledger.balance(Account::Held(self.id), currency),
+        }
+    }
+
+    /// Posts `change` to the client's funds in `currency` against `counterpart`, followed by
+    /// an entry of its own for each of `fees` debited from the available funds, so that fees
+    /// can be told apart from the transaction and reversed on their own. Refunds of fees
+    /// are negative and reverse the entry of the fee. Nothing is posted if a balance would
+    /// overflow.
+    fn apply(
+        &mut self,
+        ledger: &mut Ledger,
+        tx: TransactionId,
+        currency: Currency,
+        change: BalanceChange,
+        counterpart: Account,
+        fees: &[Amount],
+    ) -> Result<(), ErrorKind> {
+        let total = change
+            .available
+            .checked_add(change.held)
+            .ok_or(ErrorKind::Overflow)?;
+        let mut entries = vec![self.entry(
+            tx,
+            currency,
+            [
+                (Account::Available(self.id), Some(change.available)),
+                (Account::Held(self.id), Some(change.held)),
+                (counterpart, Amount::new(0).checked_sub(total)),
+            ],
+        )?];
+        for &fee in fees {
+            entries.push(self.entry(
+                tx,
+                currency,
+                [
+                    (Account::Available(self.id), Amount::new(0).checked_sub(fee)),
+                    (Account::Fees, Some(fee)),
+                ],
+            )?);
+        }
+        entries.retain(|entry| !entry.postings.is_empty());
+        ledger.post_all(entries)?;
+        self.currencies.insert(currency);
+        Ok(())
+    }
+
+    /// Builds a journal entry of the postings that are not zero, or fails if an amount
+    /// overflowed.
+    fn entry<const N: usize>(
+        &self,
+        tx: TransactionId,
+        currency: Currency,
+        postings: [(Account, Option<Amount>); N],
+    ) -> Result<JournalEntry, ErrorKind> {
+        let mut entry = JournalEntry {
+            tx,
+            postings: Vec::new(),
+        };
+        for (account, amount) in postings {
+            let amount = amount.ok_or(ErrorKind::Overflow)?;
+            if amount != Amount::new(0) {
+                entry.postings.push(Posting {
+                    account,
+                    currency,
+                    amount,
+                });
+            }
+        }
+        Ok(entry)
+    }
+
+    /// Returns the balance in `currency`, which is zero if the client never held any.
+    pub fn get_entry(&self, ledger: &Ledger, currency: Currency) -> ClientEntry {
+        let balance = self.balance(ledger, currency);
+        ClientEntry::new(
+            self.id,
+            currency,
+            balance.available,
+            balance.held,
+            self.locked,
+        )
+    }
+
+    /// Returns the balances in all currencies the client holds, ordered by currency, or the
+    /// zero balance in `default` if there are none.
+    pub fn entries(&self, ledger: &Ledger, default: Currency) -> Vec<ClientEntry> {
+        if self.currencies.is_empty() {
+            return vec![self.get_entry(ledger, default)];
+        }
+        self.currencies
+            .iter()
+            .map(|&currency| self.get_entry(ledger, currency))
+            .collect()
+    }
+
+    /// Returns the dispute state of a deposit or withdrawal that took effect.
+    pub fn transaction_state(&self, id: TransactionId) -> Option<TransactionState> {
+        self.transactions.get(&id).map(|applied| applied.state)
+    }
+
+    /// Returns the part of a deposit or withdrawal that is under open disputes.
+    pub fn disputed_amount(&self, id: TransactionId) -> Option<Amount> {
+        self.transactions.get(&id).map(|applied| applied.disputed)
+    }
+
+    /// Returns the part of a deposit or withdrawal that was charged back.
+    pub fn charged_back_amount(&self, id: TransactionId) -> Option<Amount> {
+        self.transactions
+            .get(&id)
+            .map(|applied| applied.charged_back)
+    }
+
+    fn insert_applied(
+        &mut self,
+        id: TransactionId,
+        is_withdrawal: bool,
+        amount: Amount,
+        currency: Currency,
+    ) {
+        self.transactions.insert(
+            id,
+            AppliedTransaction {
+                is_withdrawal,
+                amount,
+                currency,
+                disputed: Amount::new(0),
+                unheld: Amount::new(0),
+                charged_back: Amount::new(0),
+                fresh: amount,
+                state: TransactionState::Processed,
+                disputes: 0,
+            },
+        );
+    }
+
+    /// Returns a transaction that is currently disputed, along with `amount` of its disputed
+    /// part, or all of it.
+    fn disputed(
+        &self,
+        id: TransactionId,
+        amount: Option<Amount>,
+    ) -> Result<(&AppliedTransaction, Amount), RejectReason> {
+        let applied = self.transactions.get(&id).ok_or(RejectReason::UnknownTx)?;
+        if applied.state != TransactionState::Disputed {
+            return Err(RejectReason::NotDisputed);
+        }
+        Ok((applied, requested(amount, applied.disputed)?))
+    }
+
+    /// Ends the dispute of `amount` of a transaction, `unheld` of which was not held. The
+    /// transaction leaves the disputed state once no part of it is disputed anymore.
+    fn settle_dispute(
+        &mut self,
+        id: TransactionId,
+        amount: Amount,
+        unheld: Amount,
+        charged_back: bool,
+    ) {
+        if let Some(applied) = self.transactions.get_mut(&id) {
+            applied.disputed -= amount;
+            applied.unheld -= unheld;
+            if charged_back {
+                applied.charged_back += amount;
+            }
+            if applied.disputed == Amount::new(0) {
+                applied.state = if applied.charged_back > Amount::new(0) {
+                    TransactionState::ChargedBack
+                } else {
+                    TransactionState::Resolved
+                };
+            }
+        }
     }
 }
 
+/// Returns the amount a dispute, resolve or chargeback applies to, `limit` if none was
+/// given.
+fn requested(amount: Option<Amount>, limit: Amount) -> Result<Amount, RejectReason> {
+    match amount {
+        None => Ok(limit),
+        Some(amount) if amount == Amount::new(0) => Err(RejectReason::ZeroAmount),
+        Some(amount) if amount < Amount::new(0) => Err(RejectReason::NegativeAmount),
+        Some(amount) if amount > limit => Err(RejectReason::ExceedsDisputed),
+        Some(amount) => Ok(amount),
+    }
+}
+
+fn negate(amount: Amount) -> Result<Amount, ErrorKind> {
+    Amount::new(0)
+        .checked_sub(amount)
+        .ok_or(ErrorKind::Overflow)
+}
+
+fn allowed_when_locked(policy: &LockPolicy, tx_type: ClientTransactionType) -> bool {
+    match tx_type {
+        ClientTransactionType::Deposit | ClientTransactionType::TransferIn { .. } => {
+            policy.allow_deposits
+        }
+        ClientTransactionType::Withdrawal | ClientTransactionType::TransferOut => {
+            policy.allow_withdrawals
+        }
+        ClientTransactionType::Dispute
+        | ClientTransactionType::Resolve
+        | ClientTransactionType::Chargeback => policy.allow_disputes,
+        ClientTransactionType::Unlock => true,
+    }
+}
+
+/// Snapshot of a client's balance in a single currency.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct ClientEntry {
     id: ClientId,
+    currency: Currency,
     available: Amount,
     held: Amount,
     locked: bool,
 }
 
 impl ClientEntry {
-    pub fn new(id: ClientId, available: Amount, held: Amount, locked: bool) -> Self {
+    pub fn new(
+        id: ClientId,
+        currency: Currency,
+        available: Amount,
+        held: Amount,
+        locked: bool,
+    ) -> Self {
         Self {
             id,
+            currency,
             available,
             held,
             locked,
         }
     }
+
+    pub fn id(&self) -> ClientId {
+        self.id
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    /// Funds that can be withdrawn.
+    pub fn available(&self) -> Amount {
+        self.available
+    }
+
+    /// Funds held by open disputes.
+    pub fn held(&self) -> Amount {
+        self.held
+    }
+
+    /// Sum of available and held funds.
+    pub fn total(&self) -> Amount {
+        self.available + self.held
+    }
+
+    /// Whether the account was frozen by a chargeback.
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
 }
 
+/// Formats the entry as an output row without the currency.
 impl fmt::Display for ClientEntry {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -126,33 +630,67 @@ impl fmt::Display for ClientEntry {
             self.id,
             self.available,
             self.held,
-            self.available + self.held,
+            self.total(),
             self.locked
         )
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 pub enum ClientTransactionType {
     Deposit,
     Withdrawal,
     Dispute,
     Resolve,
     Chargeback,
+    Unlock,
+    /// Sending side of a transfer to another client.
+    TransferOut,
+    /// Receiving side of a transfer from another client.
+    TransferIn {
+        from: ClientId,
+    },
 }
 
+#[derive(Clone, Debug)]
 pub struct ClientTransaction {
     id: TransactionId,
     tx_type: ClientTransactionType,
     amount: Option<Amount>,
+    /// Fees debited along with a withdrawal or chargeback, negative ones refunded.
+    fees: Vec<Amount>,
+    /// Currency of a deposit or withdrawal, disputes use the one of the disputed transaction.
+    currency: Currency,
 }
 
 impl ClientTransaction {
+    pub fn id(&self) -> TransactionId {
+        self.id
+    }
+
+    pub fn with_currency(mut self, currency: Currency) -> Self {
+        self.currency = currency;
+        self
+    }
+
+    /// Limits a dispute, resolve or chargeback to `amount` of the transaction, if given.
+    pub fn with_amount(mut self, amount: Option<Amount>) -> Self {
+        self.amount = amount;
+        self
+    }
+
+    pub fn with_fees(mut self, fees: Vec<Amount>) -> Self {
+        self.fees = fees;
+        self
+    }
+
     pub fn deposit(id: TransactionId, amount: Amount) -> Self {
         Self {
             id,
             tx_type: ClientTransactionType::Deposit,
             amount: Some(amount),
+            fees: Vec::new(),
+            currency: Currency::default(),
         }
     }
 
@@ -161,6 +699,8 @@ impl ClientTransaction {
             id,
             tx_type: ClientTransactionType::Withdrawal,
             amount: Some(amount),
+            fees: Vec::new(),
+            currency: Currency::default(),
         }
     }
 
@@ -169,6 +709,8 @@ impl ClientTransaction {
             id,
             tx_type: ClientTransactionType::Dispute,
             amount: None,
+            fees: Vec::new(),
+            currency: Currency::default(),
         }
     }
 
@@ -177,6 +719,8 @@ impl ClientTransaction {
             id,
             tx_type: ClientTransactionType::Resolve,
             amount: None,
+            fees: Vec::new(),
+            currency: Currency::default(),
         }
     }
 
@@ -185,6 +729,38 @@ impl ClientTransaction {
             id,
             tx_type: ClientTransactionType::Chargeback,
             amount: None,
+            fees: Vec::new(),
+            currency: Currency::default(),
+        }
+    }
+
+    pub fn unlock(id: TransactionId) -> Self {
+        Self {
+            id,
+            tx_type: ClientTransactionType::Unlock,
+            amount: None,
+            fees: Vec::new(),
+            currency: Currency::default(),
+        }
+    }
+
+    pub fn transfer_out(id: TransactionId, amount: Amount) -> Self {
+        Self {
+            id,
+            tx_type: ClientTransactionType::TransferOut,
+            amount: Some(amount),
+            fees: Vec::new(),
+            currency: Currency::default(),
+        }
+    }
+
+    pub fn transfer_in(id: TransactionId, amount: Amount, from: ClientId) -> Self {
+        Self {
+            id,
+            tx_type: ClientTransactionType::TransferIn { from },
+            amount: Some(amount),
+            fees: Vec::new(),
+            currency: Currency::default(),
         }
     }
 }
@@ -192,6 +768,169 @@ impl ClientTransaction {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::amount::UnderlyingAmountType;
+    use crate::config::OverdraftPolicy;
+    use crate::dispute::{
+        DisputePolicy, HoldWithdrawalAsCredit, RedisputePolicy, RejectWithdrawalDisputes,
+        ReverseWithdrawal,
+    };
+    use proptest::prelude::*;
+    use std::ops::Deref;
+    use std::sync::Arc;
+
+    /// Client along with a ledger of its own.
+    struct TestClient {
+        client: Client,
+        ledger: Ledger,
+    }
+
+    impl TestClient {
+        fn new(id: ClientId) -> Self {
+            Self {
+                client: Client::new(id),
+                ledger: Ledger::new(),
+            }
+        }
+
+        fn add_transaction(
+            &mut self,
+            transaction: ClientTransaction,
+            config: &Config,
+        ) -> Result<Outcome, ErrorKind> {
+            self.client
+                .add_transaction(transaction, config, &mut self.ledger)
+        }
+
+        fn get_entry(&self, currency: Currency) -> ClientEntry {
+            self.client.get_entry(&self.ledger, currency)
+        }
+
+        fn entries(&self, default: Currency) -> Vec<ClientEntry> {
+            self.client.entries(&self.ledger, default)
+        }
+    }
+
+    impl Deref for TestClient {
+        type Target = Client;
+
+        fn deref(&self) -> &Client {
+            &self.client
+        }
+    }
+
+    /// Reference implementation that recomputes the balance by replaying every transaction,
+    /// the way `Client` used to do it.
+    fn replay(id: ClientId, transactions: &[ClientTransaction]) -> ClientEntry {
+        let mut available = Amount::new(0);
+        let mut held = Amount::new(0);
+        let mut locked = false;
+        // Disputed amounts, along with whether they are of a withdrawal.
+        let mut disputed: HashMap<TransactionId, (Amount, bool)> = HashMap::new();
+        let mut valid_transactions: Vec<&ClientTransaction> = Vec::new();
+
+        for tx in transactions {
+            match tx.tx_type {
+                ClientTransactionType::Deposit => {
+                    let amount = tx.amount.unwrap();
+                    if amount != Amount::new(0) {
+                        available += amount;
+                        valid_transactions.push(tx);
+                    }
+                }
+                ClientTransactionType::Withdrawal => {
+                    let amount = tx.amount.unwrap();
+                    if amount != Amount::new(0) && available >= amount && !locked {
+                        available -= amount;
+                        valid_transactions.push(tx);
+                    }
+                }
+                ClientTransactionType::Dispute => {
+                    // Repeated disputes used to hold the amount again, they are rejected now.
+                    if disputed.contains_key(&tx.id) {
+                        continue;
+                    }
+                    if let Some(tx_found) =
+                        valid_transactions.iter().find(|other| other.id == tx.id)
+                    {
+                        let amount = tx_found.amount.unwrap();
+                        // Disputed withdrawals are held as a credit, deposits are taken
+                        // from the available funds.
+                        let is_withdrawal =
+                            matches!(tx_found.tx_type, ClientTransactionType::Withdrawal);
+                        disputed.insert(tx.id, (amount, is_withdrawal));
+                        if !is_withdrawal {
+                            available -= amount;
+                        }
+                        held += amount;
+                    }
+                }
+                ClientTransactionType::Unlock
+                | ClientTransactionType::TransferOut
+                | ClientTransactionType::TransferIn { .. } => {
+                    unreachable!("unlocks and transfers are not generated")
+                }
+                ClientTransactionType::Resolve | ClientTransactionType::Chargeback => {
+                    if let Some((amount, is_withdrawal)) = disputed.remove(&tx.id) {
+                        held -= amount;
+                        let resolve = matches!(tx.tx_type, ClientTransactionType::Resolve);
+                        if resolve != is_withdrawal {
+                            available += amount;
+                        }
+                        if !resolve {
+                            locked = true;
+                        }
+                        valid_transactions.retain(|other| other.id != tx.id);
+                    }
+                }
+            }
+        }
+
+        ClientEntry::new(id, Currency::default(), available, held, locked)
+    }
+
+    fn client_transaction() -> impl Strategy<Value = ClientTransaction> {
+        let id = 0..64u32;
+        let amount = (0..50_000 as UnderlyingAmountType).prop_map(Amount::new);
+        prop_oneof![
+            (id.clone(), amount.clone())
+                .prop_map(|(id, amount)| ClientTransaction::deposit(id, amount)),
+            (id.clone(), amount).prop_map(|(id, amount)| ClientTransaction::withdrawal(id, amount)),
+            id.clone().prop_map(ClientTransaction::dispute),
+            id.clone().prop_map(ClientTransaction::resolve),
+            id.prop_map(ClientTransaction::chargeback),
+        ]
+    }
+
+    /// Gives every deposit and withdrawal a unique id and points disputes, resolves and
+    /// chargebacks at earlier transactions, since transaction ids are unique.
+    fn with_unique_ids(mut transactions: Vec<ClientTransaction>) -> Vec<ClientTransaction> {
+        for (i, tx) in transactions.iter_mut().enumerate() {
+            tx.id = match tx.tx_type {
+                ClientTransactionType::Deposit | ClientTransactionType::Withdrawal => i as u32,
+                _ => tx.id % (i as u32 + 1),
+            };
+        }
+        transactions
+    }
+
+    proptest! {
+        #[test]
+        fn incremental_balance_matches_replay(
+            transactions in proptest::collection::vec(client_transaction(), 0..64)
+                .prop_map(with_unique_ids)
+        ) {
+            let mut client = TestClient::new(1);
+            let config = Config::default();
+            for (i, tx) in transactions.iter().enumerate() {
+                client.add_transaction(tx.clone(), &config).unwrap();
+                prop_assert_eq!(
+                    client.get_entry(Currency::default()).to_string(),
+                    replay(1, &transactions[..=i]).to_string()
+                );
+            }
+            prop_assert!(client.ledger.verify());
+        }
+    }
 
     #[test]
     fn formatting() {
@@ -199,97 +938,963 @@ mod tests {
         let available = Amount::new(12345);
         let held = Amount::new(1);
         let locked = false;
-        let entry = ClientEntry::new(id, available, held, locked);
+        let entry = ClientEntry::new(id, Currency::default(), available, held, locked);
         assert_eq!(format!("{}", entry), "1,1.2345,0.0001,1.2346,false");
     }
 
     #[test]
     fn deposit_resolve() {
-        let mut client = Client::new(1);
+        let mut client = TestClient::new(1);
+        let config = Config::default();
 
-        client.add_transaction(ClientTransaction::deposit(1, Amount::new(100000)));
-        assert_eq!(format!("{}", client.get_entry()), "1,10,0,10,false");
+        client
+            .add_transaction(ClientTransaction::deposit(1, Amount::new(100000)), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,10,0,10,false"
+        );
 
-        client.add_transaction(ClientTransaction::dispute(1));
-        assert_eq!(format!("{}", client.get_entry()), "1,0,10,10,false");
+        client
+            .add_transaction(ClientTransaction::dispute(1), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,0,10,10,false"
+        );
 
-        client.add_transaction(ClientTransaction::resolve(1));
-        assert_eq!(format!("{}", client.get_entry()), "1,10,0,10,false");
+        client
+            .add_transaction(ClientTransaction::resolve(1), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,10,0,10,false"
+        );
     }
 
     #[test]
     fn deposit_chargeback() {
-        let mut client = Client::new(1);
+        let mut client = TestClient::new(1);
+        let config = Config::default();
 
-        client.add_transaction(ClientTransaction::deposit(1, Amount::new(100000)));
-        assert_eq!(format!("{}", client.get_entry()), "1,10,0,10,false");
+        client
+            .add_transaction(ClientTransaction::deposit(1, Amount::new(100000)), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,10,0,10,false"
+        );
 
-        client.add_transaction(ClientTransaction::dispute(1));
-        assert_eq!(format!("{}", client.get_entry()), "1,0,10,10,false");
+        client
+            .add_transaction(ClientTransaction::dispute(1), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,0,10,10,false"
+        );
 
-        client.add_transaction(ClientTransaction::chargeback(1));
-        assert_eq!(format!("{}", client.get_entry()), "1,0,0,0,true");
+        client
+            .add_transaction(ClientTransaction::chargeback(1), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,0,0,0,true"
+        );
     }
 
     #[test]
     fn withdrawal_resolve() {
-        let mut client = Client::new(1);
+        let mut client = TestClient::new(1);
+        let config = Config::default();
 
-        client.add_transaction(ClientTransaction::deposit(1, Amount::new(100000)));
-        assert_eq!(format!("{}", client.get_entry()), "1,10,0,10,false");
+        client
+            .add_transaction(ClientTransaction::deposit(1, Amount::new(100000)), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,10,0,10,false"
+        );
 
-        client.add_transaction(ClientTransaction::withdrawal(2, Amount::new(20000)));
-        assert_eq!(format!("{}", client.get_entry()), "1,8,0,8,false");
+        client
+            .add_transaction(
+                ClientTransaction::withdrawal(2, Amount::new(20000)),
+                &config,
+            )
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,8,0,8,false"
+        );
 
-        client.add_transaction(ClientTransaction::dispute(2));
-        assert_eq!(format!("{}", client.get_entry()), "1,10,-2,8,false");
+        client
+            .add_transaction(ClientTransaction::dispute(2), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,8,2,10,false"
+        );
 
-        client.add_transaction(ClientTransaction::resolve(2));
-        assert_eq!(format!("{}", client.get_entry()), "1,8,0,8,false");
+        client
+            .add_transaction(ClientTransaction::resolve(2), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,8,0,8,false"
+        );
     }
 
     #[test]
     fn withdrawal_chargeback() {
-        let mut client = Client::new(1);
+        let mut client = TestClient::new(1);
+        let config = Config::default();
 
-        client.add_transaction(ClientTransaction::deposit(1, Amount::new(100000)));
-        assert_eq!(format!("{}", client.get_entry()), "1,10,0,10,false");
+        client
+            .add_transaction(ClientTransaction::deposit(1, Amount::new(100000)), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,10,0,10,false"
+        );
 
-        client.add_transaction(ClientTransaction::withdrawal(2, Amount::new(20000)));
-        assert_eq!(format!("{}", client.get_entry()), "1,8,0,8,false");
+        client
+            .add_transaction(
+                ClientTransaction::withdrawal(2, Amount::new(20000)),
+                &config,
+            )
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,8,0,8,false"
+        );
 
-        client.add_transaction(ClientTransaction::dispute(2));
-        assert_eq!(format!("{}", client.get_entry()), "1,10,-2,8,false");
+        client
+            .add_transaction(ClientTransaction::dispute(2), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,8,2,10,false"
+        );
 
-        client.add_transaction(ClientTransaction::chargeback(2));
-        assert_eq!(format!("{}", client.get_entry()), "1,10,0,10,true");
+        client
+            .add_transaction(ClientTransaction::chargeback(2), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,10,0,10,true"
+        );
     }
 
     #[test]
     fn cannot_spend_over_available_funds() {
-        let mut client = Client::new(1);
+        let mut client = TestClient::new(1);
+        let config = Config::default();
 
-        client.add_transaction(ClientTransaction::deposit(1, Amount::new(10000)));
-        assert_eq!(format!("{}", client.get_entry()), "1,1,0,1,false");
+        client
+            .add_transaction(ClientTransaction::deposit(1, Amount::new(10000)), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,1,0,1,false"
+        );
 
-        client.add_transaction(ClientTransaction::withdrawal(2, Amount::new(20000)));
-        assert_eq!(format!("{}", client.get_entry()), "1,1,0,1,false");
+        client
+            .add_transaction(
+                ClientTransaction::withdrawal(2, Amount::new(20000)),
+                &config,
+            )
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,1,0,1,false"
+        );
     }
 
     #[test]
     fn negative_available_funds() {
-        let mut client = Client::new(1);
+        let mut client = TestClient::new(1);
+        let config = Config::default();
+
+        client
+            .add_transaction(ClientTransaction::deposit(1, Amount::new(10000)), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,1,0,1,false"
+        );
+
+        client
+            .add_transaction(ClientTransaction::withdrawal(2, Amount::new(5000)), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,0.5,0,0.5,false"
+        );
+
+        client
+            .add_transaction(ClientTransaction::dispute(1), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,-0.5,1,0.5,false"
+        );
+
+        client
+            .add_transaction(ClientTransaction::chargeback(1), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,-0.5,0,-0.5,true"
+        );
+    }
+
+    #[test]
+    fn rejections() {
+        let mut client = TestClient::new(1);
+        let config = Config::default();
+
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::deposit(1, Amount::new(0)), &config)
+                .unwrap(),
+            Outcome::Rejected(RejectReason::ZeroAmount)
+        );
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::deposit(1, Amount::new(-10000)), &config)
+                .unwrap(),
+            Outcome::Rejected(RejectReason::NegativeAmount)
+        );
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::deposit(1, Amount::new(10000)), &config)
+                .unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            client
+                .add_transaction(
+                    ClientTransaction::withdrawal(2, Amount::new(-30000)),
+                    &config
+                )
+                .unwrap(),
+            Outcome::Rejected(RejectReason::NegativeAmount)
+        );
+        assert_eq!(
+            client
+                .add_transaction(
+                    ClientTransaction::withdrawal(2, Amount::new(20000)),
+                    &config
+                )
+                .unwrap(),
+            Outcome::Rejected(RejectReason::InsufficientFunds)
+        );
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::dispute(2), &config)
+                .unwrap(),
+            Outcome::Rejected(RejectReason::UnknownTx)
+        );
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::resolve(1), &config)
+                .unwrap(),
+            Outcome::Rejected(RejectReason::NotDisputed)
+        );
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::dispute(1), &config)
+                .unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::dispute(1), &config)
+                .unwrap(),
+            Outcome::Rejected(RejectReason::AlreadyDisputed)
+        );
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,0,1,1,false"
+        );
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::chargeback(1), &config)
+                .unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::chargeback(1), &config)
+                .unwrap(),
+            Outcome::Rejected(RejectReason::NotDisputed)
+        );
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::withdrawal(3, Amount::new(1)), &config)
+                .unwrap(),
+            Outcome::Rejected(RejectReason::AccountLocked)
+        );
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,0,0,0,true"
+        );
+    }
+
+    #[test]
+    fn dispute_lifecycle() {
+        let mut client = TestClient::new(1);
+        let config = Config::default();
+        let apply = |client: &mut TestClient, tx| client.add_transaction(tx, &config).unwrap();
+
+        assert_eq!(client.transaction_state(1), None);
+        apply(
+            &mut client,
+            ClientTransaction::deposit(1, Amount::new(10000)),
+        );
+        assert_eq!(
+            client.transaction_state(1),
+            Some(TransactionState::Processed)
+        );
 
-        client.add_transaction(ClientTransaction::deposit(1, Amount::new(10000)));
-        assert_eq!(format!("{}", client.get_entry()), "1,1,0,1,false");
+        apply(&mut client, ClientTransaction::dispute(1));
+        assert_eq!(
+            client.transaction_state(1),
+            Some(TransactionState::Disputed)
+        );
+
+        apply(&mut client, ClientTransaction::resolve(1));
+        assert_eq!(
+            client.transaction_state(1),
+            Some(TransactionState::Resolved)
+        );
+        assert_eq!(
+            apply(&mut client, ClientTransaction::dispute(1)),
+            Outcome::Rejected(RejectReason::RedisputeNotAllowed)
+        );
+        assert_eq!(
+            apply(&mut client, ClientTransaction::resolve(1)),
+            Outcome::Rejected(RejectReason::NotDisputed)
+        );
+
+        apply(
+            &mut client,
+            ClientTransaction::deposit(2, Amount::new(10000)),
+        );
+        apply(&mut client, ClientTransaction::dispute(2));
+        apply(&mut client, ClientTransaction::chargeback(2));
+        assert_eq!(
+            client.transaction_state(2),
+            Some(TransactionState::ChargedBack)
+        );
+        assert_eq!(
+            apply(&mut client, ClientTransaction::dispute(2)),
+            Outcome::Rejected(RejectReason::AlreadyChargedBack)
+        );
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,1,0,1,true"
+        );
+    }
+
+    #[test]
+    fn redisputes_up_to_limit() {
+        let mut client = TestClient::new(1);
+        let config = Config {
+            redisputes: RedisputePolicy::UpTo(2),
+            ..Config::default()
+        };
+        let apply = |client: &mut TestClient, tx| client.add_transaction(tx, &config).unwrap();
+
+        apply(
+            &mut client,
+            ClientTransaction::deposit(1, Amount::new(10000)),
+        );
+        for _ in 0..2 {
+            assert_eq!(
+                apply(&mut client, ClientTransaction::dispute(1)),
+                Outcome::Applied
+            );
+            assert_eq!(
+                format!("{}", client.get_entry(Currency::default())),
+                "1,0,1,1,false"
+            );
+            assert_eq!(
+                apply(&mut client, ClientTransaction::resolve(1)),
+                Outcome::Applied
+            );
+            assert_eq!(
+                format!("{}", client.get_entry(Currency::default())),
+                "1,1,0,1,false"
+            );
+        }
+        assert_eq!(
+            apply(&mut client, ClientTransaction::dispute(1)),
+            Outcome::Rejected(RejectReason::RedisputeNotAllowed)
+        );
+    }
+
+    /// Deposits 10, withdraws 2, disputes the withdrawal and then either resolves it or
+    /// charges it back, returning the balances after the dispute and after it ended.
+    fn dispute_withdrawal(policy: impl DisputePolicy + 'static, chargeback: bool) -> [String; 2] {
+        let mut client = TestClient::new(1);
+        let config = Config {
+            dispute_policy: Arc::new(policy),
+            ..Config::default()
+        };
+        for tx in [
+            ClientTransaction::deposit(1, Amount::new(100000)),
+            ClientTransaction::withdrawal(2, Amount::new(20000)),
+            ClientTransaction::dispute(2),
+        ] {
+            client.add_transaction(tx, &config).unwrap();
+        }
+        let disputed = client.get_entry(Currency::default()).to_string();
+        let end = if chargeback {
+            ClientTransaction::chargeback(2)
+        } else {
+            ClientTransaction::resolve(2)
+        };
+        client.add_transaction(end, &config).unwrap();
+        [disputed, client.get_entry(Currency::default()).to_string()]
+    }
+
+    #[test]
+    fn withdrawal_dispute_held_as_credit() {
+        assert_eq!(
+            dispute_withdrawal(HoldWithdrawalAsCredit, false),
+            ["1,8,2,10,false", "1,8,0,8,false"]
+        );
+        assert_eq!(
+            dispute_withdrawal(HoldWithdrawalAsCredit, true),
+            ["1,8,2,10,false", "1,10,0,10,true"]
+        );
+    }
+
+    #[test]
+    fn withdrawal_credit_is_never_held_negative() {
+        let part = |amount| Some(Amount::new(amount));
+        for end in [ClientTransaction::resolve, ClientTransaction::chargeback] {
+            let mut client = TestClient::new(1);
+            let config = Config::default();
+            for tx in [
+                ClientTransaction::deposit(1, Amount::new(100000)),
+                ClientTransaction::withdrawal(2, Amount::new(50000)),
+                ClientTransaction::dispute(2).with_amount(part(20000)),
+                ClientTransaction::dispute(2),
+                end(2).with_amount(part(10000)),
+                end(2),
+            ] {
+                client.add_transaction(tx, &config).unwrap();
+                let held = client.get_entry(Currency::default()).held();
+                assert!(held >= Amount::new(0), "held {} is negative", held);
+            }
+        }
+    }
+
+    #[test]
+    fn withdrawal_dispute_reversed() {
+        assert_eq!(
+            dispute_withdrawal(ReverseWithdrawal, false),
+            ["1,10,0,10,false", "1,8,0,8,false"]
+        );
+        assert_eq!(
+            dispute_withdrawal(ReverseWithdrawal, true),
+            ["1,10,0,10,false", "1,10,0,10,true"]
+        );
+    }
+
+    #[test]
+    fn withdrawal_dispute_rejected() {
+        let mut client = TestClient::new(1);
+        let config = Config {
+            dispute_policy: Arc::new(RejectWithdrawalDisputes),
+            ..Config::default()
+        };
+        client
+            .add_transaction(ClientTransaction::deposit(1, Amount::new(100000)), &config)
+            .unwrap();
+        client
+            .add_transaction(
+                ClientTransaction::withdrawal(2, Amount::new(20000)),
+                &config,
+            )
+            .unwrap();
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::dispute(2), &config)
+                .unwrap(),
+            Outcome::Rejected(RejectReason::NotDisputable)
+        );
+        assert_eq!(
+            client.transaction_state(2),
+            Some(TransactionState::Processed)
+        );
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::dispute(1), &config)
+                .unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,-2,10,8,false"
+        );
+    }
+
+    #[test]
+    fn lock_policy() {
+        let mut client = TestClient::new(1);
+        let mut config = Config::default();
+        for tx in [
+            ClientTransaction::deposit(1, Amount::new(10000)),
+            ClientTransaction::deposit(2, Amount::new(10000)),
+            ClientTransaction::dispute(1),
+            ClientTransaction::chargeback(1),
+        ] {
+            client.add_transaction(tx, &config).unwrap();
+        }
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,1,0,1,true"
+        );
+
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::withdrawal(3, Amount::new(1)), &config)
+                .unwrap(),
+            Outcome::Rejected(RejectReason::AccountLocked)
+        );
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::deposit(4, Amount::new(10000)), &config)
+                .unwrap(),
+            Outcome::Applied
+        );
+
+        config.lock = LockPolicy::frozen();
+        for tx in [
+            ClientTransaction::deposit(5, Amount::new(10000)),
+            ClientTransaction::dispute(2),
+        ] {
+            assert_eq!(
+                client.add_transaction(tx, &config).unwrap(),
+                Outcome::Rejected(RejectReason::AccountLocked)
+            );
+        }
+
+        config.lock.allow_withdrawals = true;
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::withdrawal(6, Amount::new(5000)), &config)
+                .unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,1.5,0,1.5,true"
+        );
+    }
+
+    #[test]
+    fn unlock() {
+        let mut client = TestClient::new(1);
+        let config = Config::default();
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::unlock(1), &config)
+                .unwrap(),
+            Outcome::Rejected(RejectReason::NotLocked)
+        );
+        for tx in [
+            ClientTransaction::deposit(2, Amount::new(20000)),
+            ClientTransaction::deposit(3, Amount::new(10000)),
+            ClientTransaction::dispute(3),
+            ClientTransaction::chargeback(3),
+            ClientTransaction::unlock(4),
+        ] {
+            assert_eq!(
+                client.add_transaction(tx, &config).unwrap(),
+                Outcome::Applied
+            );
+        }
+        assert_eq!(
+            client
+                .add_transaction(
+                    ClientTransaction::withdrawal(5, Amount::new(20000)),
+                    &config
+                )
+                .unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,0,0,0,false"
+        );
+    }
+
+    #[test]
+    fn overdraft_limit() {
+        let overdrawn = |mode| {
+            let mut client = TestClient::new(1);
+            let config = Config {
+                overdraft: OverdraftPolicy {
+                    limit: Some(Amount::new(5000)),
+                    mode,
+                    ..OverdraftPolicy::default()
+                },
+                ..Config::default()
+            };
+            for tx in [
+                ClientTransaction::deposit(1, Amount::new(20000)),
+                ClientTransaction::withdrawal(2, Amount::new(15000)),
+            ] {
+                client.add_transaction(tx, &config).unwrap();
+            }
+            let outcome = client
+                .add_transaction(ClientTransaction::dispute(1), &config)
+                .unwrap();
+            (client, config, outcome)
+        };
+
+        let (client, _, outcome) = overdrawn(OverdraftMode::Refuse);
+        assert_eq!(outcome, Outcome::Rejected(RejectReason::OverdraftLimit));
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,0.5,0,0.5,false"
+        );
+
+        let (mut client, config, outcome) = overdrawn(OverdraftMode::PartialHold);
+        assert_eq!(outcome, Outcome::PartiallyHeld(Amount::new(10000)));
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,-0.5,1,0.5,false"
+        );
+        client
+            .add_transaction(ClientTransaction::resolve(1), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,0.5,0,0.5,false"
+        );
+
+        let (mut client, config, _) = overdrawn(OverdraftMode::PartialHold);
+        client
+            .add_transaction(ClientTransaction::chargeback(1), &config)
+            .unwrap();
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,-1.5,0,-1.5,true"
+        );
+
+        // Disputes within the limit are held in full.
+        let mut client = TestClient::new(1);
+        let config = Config {
+            overdraft: OverdraftPolicy {
+                limit: Some(Amount::new(0)),
+                ..OverdraftPolicy::default()
+            },
+            ..Config::default()
+        };
+        client
+            .add_transaction(ClientTransaction::deposit(1, Amount::new(10000)), &config)
+            .unwrap();
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::dispute(1), &config)
+                .unwrap(),
+            Outcome::Applied
+        );
+    }
+
+    #[test]
+    fn partial_disputes() {
+        let mut client = TestClient::new(1);
+        let config = Config::default();
+        let apply = |client: &mut TestClient, tx| client.add_transaction(tx, &config).unwrap();
+        let part = |amount| Some(Amount::new(amount));
+
+        apply(
+            &mut client,
+            ClientTransaction::deposit(1, Amount::new(100000)),
+        );
+        for amount in [30000, 20000] {
+            assert_eq!(
+                apply(
+                    &mut client,
+                    ClientTransaction::dispute(1).with_amount(part(amount))
+                ),
+                Outcome::Applied
+            );
+        }
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,5,5,10,false"
+        );
+        assert_eq!(client.disputed_amount(1), part(50000));
+
+        for (tx, reason) in [
+            (
+                ClientTransaction::dispute(1).with_amount(part(60000)),
+                RejectReason::ExceedsDisputed,
+            ),
+            (
+                ClientTransaction::resolve(1).with_amount(part(60000)),
+                RejectReason::ExceedsDisputed,
+            ),
+            (
+                ClientTransaction::chargeback(1).with_amount(part(0)),
+                RejectReason::ZeroAmount,
+            ),
+            (
+                ClientTransaction::dispute(1).with_amount(part(-1)),
+                RejectReason::NegativeAmount,
+            ),
+        ] {
+            assert_eq!(apply(&mut client, tx), Outcome::Rejected(reason));
+        }
+
+        // Resolving part of the disputes keeps the transaction disputed.
+        apply(
+            &mut client,
+            ClientTransaction::resolve(1).with_amount(part(10000)),
+        );
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,6,4,10,false"
+        );
+        assert_eq!(
+            client.transaction_state(1),
+            Some(TransactionState::Disputed)
+        );
+
+        // The resolved part was disputed before, only the part never disputed can be.
+        assert_eq!(
+            apply(&mut client, ClientTransaction::dispute(1)),
+            Outcome::Rejected(RejectReason::RedisputeNotAllowed)
+        );
+        assert_eq!(
+            apply(
+                &mut client,
+                ClientTransaction::dispute(1).with_amount(part(50000))
+            ),
+            Outcome::Applied
+        );
+        assert_eq!(client.disputed_amount(1), part(90000));
+
+        apply(
+            &mut client,
+            ClientTransaction::chargeback(1).with_amount(part(40000)),
+        );
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,1,5,6,true"
+        );
+        apply(&mut client, ClientTransaction::resolve(1));
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,6,0,6,true"
+        );
+        assert_eq!(
+            client.transaction_state(1),
+            Some(TransactionState::ChargedBack)
+        );
+        assert_eq!(client.charged_back_amount(1), part(40000));
+        assert_eq!(
+            apply(&mut client, ClientTransaction::dispute(1)),
+            Outcome::Rejected(RejectReason::RedisputeNotAllowed)
+        );
+    }
+
+    #[test]
+    fn disputes_funds_never_disputed() {
+        let mut client = TestClient::new(1);
+        let config = Config::default();
+        let apply = |client: &mut TestClient, tx| client.add_transaction(tx, &config).unwrap();
+        let part = |amount| Some(Amount::new(amount));
+
+        apply(
+            &mut client,
+            ClientTransaction::deposit(1, Amount::new(100000)),
+        );
+        apply(
+            &mut client,
+            ClientTransaction::dispute(1).with_amount(part(40000)),
+        );
+        apply(&mut client, ClientTransaction::resolve(1));
+        assert_eq!(
+            apply(
+                &mut client,
+                ClientTransaction::dispute(1).with_amount(part(60000))
+            ),
+            Outcome::Applied
+        );
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,4,6,10,false"
+        );
+        assert_eq!(
+            apply(
+                &mut client,
+                ClientTransaction::dispute(1).with_amount(part(10000))
+            ),
+            Outcome::Rejected(RejectReason::RedisputeNotAllowed)
+        );
+
+        // A partial chargeback leaves the rest of the deposit to be disputed.
+        apply(
+            &mut client,
+            ClientTransaction::deposit(2, Amount::new(100000)),
+        );
+        apply(
+            &mut client,
+            ClientTransaction::dispute(2).with_amount(part(40000)),
+        );
+        apply(&mut client, ClientTransaction::chargeback(2));
+        assert_eq!(
+            client.transaction_state(2),
+            Some(TransactionState::ChargedBack)
+        );
+        assert_eq!(
+            apply(&mut client, ClientTransaction::dispute(2)),
+            Outcome::Applied
+        );
+        assert_eq!(client.disputed_amount(2), part(60000));
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,4,12,16,true"
+        );
+    }
+
+    #[test]
+    fn partial_disputes_of_withdrawals_and_overdrafts() {
+        let mut client = TestClient::new(1);
+        let config = Config {
+            overdraft: OverdraftPolicy {
+                limit: Some(Amount::new(0)),
+                mode: OverdraftMode::PartialHold,
+                ..OverdraftPolicy::default()
+            },
+            ..Config::default()
+        };
+        let apply = |client: &mut TestClient, tx| client.add_transaction(tx, &config).unwrap();
+        let part = |amount| Some(Amount::new(amount));
+        for tx in [
+            ClientTransaction::deposit(1, Amount::new(100000)),
+            ClientTransaction::withdrawal(2, Amount::new(80000)),
+            ClientTransaction::dispute(2).with_amount(part(30000)),
+        ] {
+            apply(&mut client, tx);
+        }
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,2,3,5,false"
+        );
+        apply(&mut client, ClientTransaction::resolve(2));
+
+        // Only 2 of the disputed deposit can be held, resolving releases held funds first.
+        assert_eq!(
+            apply(
+                &mut client,
+                ClientTransaction::dispute(1).with_amount(part(30000))
+            ),
+            Outcome::PartiallyHeld(Amount::new(20000))
+        );
+        apply(
+            &mut client,
+            ClientTransaction::resolve(1).with_amount(part(20000)),
+        );
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,2,0,2,false"
+        );
+        apply(&mut client, ClientTransaction::chargeback(1));
+        assert_eq!(
+            format!("{}", client.get_entry(Currency::default())),
+            "1,1,0,1,true"
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let mut client = TestClient::new(1);
+        let config = Config::default();
+        let max = Amount::new(UnderlyingAmountType::MAX);
+        client
+            .add_transaction(ClientTransaction::deposit(1, max), &config)
+            .unwrap();
+        assert!(matches!(
+            client.add_transaction(ClientTransaction::deposit(2, Amount::new(1)), &config),
+            Err(ErrorKind::Overflow)
+        ));
+        assert_eq!(client.get_entry(Currency::default()).available(), max);
+
+        // Disputing the withdrawal credits it back, which no longer fits.
+        for tx in [
+            ClientTransaction::withdrawal(3, Amount::new(1)),
+            ClientTransaction::deposit(4, Amount::new(1)),
+        ] {
+            client.add_transaction(tx, &config).unwrap();
+        }
+        assert!(matches!(
+            client.add_transaction(ClientTransaction::dispute(3), &config),
+            Err(ErrorKind::Overflow)
+        ));
+        assert_eq!(
+            client.transaction_state(3),
+            Some(TransactionState::Processed)
+        );
+        assert_eq!(
+            client.get_entry(Currency::default()),
+            ClientEntry::new(1, Currency::default(), max, Amount::new(0), false)
+        );
+    }
+
+    #[test]
+    fn currencies() {
+        let mut client = TestClient::new(1);
+        let config = Config::default();
+        let usd = Currency::default();
+        let btc: Currency = "BTC".parse().unwrap();
+        for tx in [
+            ClientTransaction::deposit(1, Amount::new(10000)),
+            ClientTransaction::deposit(2, Amount::with_scale(50000000, 8)).with_currency(btc),
+        ] {
+            client.add_transaction(tx, &config).unwrap();
+        }
+        assert_eq!(
+            client
+                .add_transaction(
+                    ClientTransaction::withdrawal(3, Amount::new(20000)).with_currency(btc),
+                    &config
+                )
+                .unwrap(),
+            Outcome::Rejected(RejectReason::InsufficientFunds)
+        );
 
-        client.add_transaction(ClientTransaction::withdrawal(2, Amount::new(5000)));
-        assert_eq!(format!("{}", client.get_entry()), "1,0.5,0,0.5,false");
+        // The dispute row names no currency, the funds are held in the one of the deposit.
+        client
+            .add_transaction(ClientTransaction::dispute(2), &config)
+            .unwrap();
+        let entries: Vec<_> = client
+            .entries(usd)
+            .iter()
+            .map(|entry| format!("{} {}", entry.currency(), entry))
+            .collect();
+        assert_eq!(entries, ["BTC 1,0,0.5,0.5,false", "USD 1,1,0,1,false"]);
 
-        client.add_transaction(ClientTransaction::dispute(1));
-        assert_eq!(format!("{}", client.get_entry()), "1,-0.5,1,0.5,false");
+        // The chargeback locks the account in every currency.
+        client
+            .add_transaction(ClientTransaction::chargeback(2), &config)
+            .unwrap();
+        assert_eq!(
+            client
+                .add_transaction(ClientTransaction::withdrawal(4, Amount::new(1)), &config)
+                .unwrap(),
+            Outcome::Rejected(RejectReason::AccountLocked)
+        );
+        assert_eq!(format!("{}", client.get_entry(btc)), "1,0,0,0,true");
 
-        client.add_transaction(ClientTransaction::chargeback(1));
-        assert_eq!(format!("{}", client.get_entry()), "1,-0.5,0,-0.5,true");
+        let empty = TestClient::new(2);
+        assert_eq!(empty.entries(btc), [empty.get_entry(btc)]);
     }
 }
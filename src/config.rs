@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::amount::{ExcessPrecision, ParseAmountError, RoundingMode, DECIMAL_PLACES};
+use crate::dispute::{DisputePolicy, HoldWithdrawalAsCredit, RedisputePolicy};
+use crate::fee::FeeSchedule;
+use crate::{Amount, ClientId, Currency};
+
+/// Rules the [`Database`](crate::Database) applies on top of the basic transaction semantics.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub duplicates: DuplicatePolicy,
+    pub redisputes: RedisputePolicy,
+    /// How disputes of withdrawals affect balances, [`HoldWithdrawalAsCredit`] by default.
+    pub dispute_policy: Arc<dyn DisputePolicy>,
+    pub lock: LockPolicy,
+    pub overdraft: OverdraftPolicy,
+    pub fees: FeeSchedule,
+    /// Currency of rows that do not name one, [`Currency::default`] unless configured.
+    pub currency: Currency,
+    pub precision: Precision,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            duplicates: DuplicatePolicy::default(),
+            redisputes: RedisputePolicy::default(),
+            dispute_policy: Arc::new(HoldWithdrawalAsCredit),
+            lock: LockPolicy::default(),
+            overdraft: OverdraftPolicy::default(),
+            fees: FeeSchedule::default(),
+            currency: Currency::default(),
+            precision: Precision::default(),
+        }
+    }
+}
+
+/// Decimal places of the currencies amounts in the input are given in.
+///
+/// Settling JPY calls for a scale of 0, USD for 2 and BTC for 8. Large scales leave little
+/// room for the integer part unless the `i128` feature is enabled. All scales are at most
+/// [`MAX_SCALE`](crate::amount::MAX_SCALE).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Precision {
+    /// Number of decimal places of currencies not in `scales`, [`DECIMAL_PLACES`] by default.
+    pub scale: u32,
+    /// Number of decimal places of individual currencies.
+    pub scales: HashMap<Currency, u32>,
+    /// What to do with amounts that have more decimal places, rejected by default.
+    pub excess: ExcessPrecision,
+}
+
+impl Precision {
+    pub fn scale_for(&self, currency: Currency) -> u32 {
+        self.scales.get(&currency).copied().unwrap_or(self.scale)
+    }
+
+    pub fn parse(&self, s: &str, currency: Currency) -> Result<Amount, ParseAmountError> {
+        Amount::parse(s, self.scale_for(currency), self.excess)
+    }
+
+    /// Brings an amount parsed at the scale it was written with to the scale of `currency`,
+    /// handling further decimal places like [`parse`](Precision::parse) does.
+    pub fn rescale(&self, amount: Amount, currency: Currency) -> Result<Amount, ParseAmountError> {
+        let scale = self.scale_for(currency);
+        let mode = match self.excess {
+            ExcessPrecision::Reject if amount.scale() > scale => {
+                return Err(ParseAmountError::TooManyDecimalPlaces(scale))
+            }
+            ExcessPrecision::Reject => RoundingMode::HalfEven,
+            ExcessPrecision::Round(mode) => mode,
+        };
+        amount
+            .rescale(scale, mode)
+            .ok_or(ParseAmountError::Overflow)
+    }
+}
+
+impl Default for Precision {
+    fn default() -> Self {
+        Self {
+            scale: DECIMAL_PLACES,
+            scales: HashMap::new(),
+            excess: ExcessPrecision::Reject,
+        }
+    }
+}
+
+/// How to treat a deposit or withdrawal that reuses the id of an earlier transaction.
+///
+/// Transaction ids are global, so a reused id is a duplicate even if it names another client.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Reject every reuse with [`RejectReason::DuplicateTxId`](crate::RejectReason::DuplicateTxId).
+    #[default]
+    Reject,
+    /// Drop exact repeats of an earlier transaction as [`Outcome::Duplicate`](crate::Outcome::Duplicate)
+    /// and reject reuse with different contents.
+    Deduplicate,
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(DuplicatePolicy::Reject),
+            "deduplicate" => Ok(DuplicatePolicy::Deduplicate),
+            _ => Err(format!(
+                "invalid duplicate policy `{}`, expected `reject` or `deduplicate`",
+                s
+            )),
+        }
+    }
+}
+
+/// Which transactions an account locked by a chargeback still accepts.
+///
+/// By default a locked account only refuses withdrawals. It stays locked until an
+/// [`Unlock`](crate::Transaction::Unlock) transaction reinstates it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LockPolicy {
+    pub allow_deposits: bool,
+    pub allow_withdrawals: bool,
+    /// Whether disputes, resolves and chargebacks are allowed.
+    pub allow_disputes: bool,
+}
+
+impl LockPolicy {
+    /// A policy under which a locked account refuses all transactions until it is unlocked.
+    pub fn frozen() -> Self {
+        Self {
+            allow_deposits: false,
+            allow_withdrawals: false,
+            allow_disputes: false,
+        }
+    }
+}
+
+impl Default for LockPolicy {
+    fn default() -> Self {
+        Self {
+            allow_deposits: true,
+            allow_withdrawals: false,
+            allow_disputes: true,
+        }
+    }
+}
+
+/// Limits how far disputes of deposits may push a client's available funds below zero.
+///
+/// Limits are given as non-negative amounts, a limit of 5 lets the available funds drop to
+/// -5. Without any limit, which is the default, disputes are never refused.
+#[derive(Clone, Debug, Default)]
+pub struct OverdraftPolicy {
+    /// Limit for clients without one of their own.
+    pub limit: Option<Amount>,
+    /// Limits overriding `limit` for individual clients.
+    pub client_limits: HashMap<ClientId, Amount>,
+    pub mode: OverdraftMode,
+}
+
+impl OverdraftPolicy {
+    pub fn limit_for(&self, client: ClientId) -> Option<Amount> {
+        self.client_limits.get(&client).copied().or(self.limit)
+    }
+}
+
+/// What happens to a dispute that would exceed the overdraft limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverdraftMode {
+    /// Reject the dispute with [`RejectReason::OverdraftLimit`](crate::RejectReason::OverdraftLimit).
+    #[default]
+    Refuse,
+    /// Hold only as much as the limit allows, reported as
+    /// [`Outcome::PartiallyHeld`](crate::Outcome::PartiallyHeld). The rest is taken from the
+    /// available funds if the dispute is charged back.
+    PartialHold,
+}
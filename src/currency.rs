@@ -0,0 +1,90 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Longest currency code accepted.
+pub const MAX_CODE_LEN: usize = 8;
+
+/// Code of the currency an amount is held in, such as `USD` or `BTC`.
+///
+/// Codes are up to [`MAX_CODE_LEN`] ASCII letters and digits, kept in upper case. They are
+/// stored inline so that the type stays `Copy`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Currency {
+    code: [u8; MAX_CODE_LEN],
+}
+
+impl Currency {
+    pub fn as_str(&self) -> &str {
+        let len = self
+            .code
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(MAX_CODE_LEN);
+        std::str::from_utf8(&self.code[..len]).expect("codes are ASCII")
+    }
+}
+
+/// `USD`, the currency of rows that do not name one unless configured otherwise.
+impl Default for Currency {
+    fn default() -> Self {
+        "USD".parse().expect("valid code")
+    }
+}
+
+impl FromStr for Currency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || s.len() > MAX_CODE_LEN || !s.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(format!(
+                "invalid currency `{}`, expected up to {} letters and digits",
+                s, MAX_CODE_LEN
+            ));
+        }
+        let mut code = [0; MAX_CODE_LEN];
+        for (dst, src) in code.iter_mut().zip(s.bytes()) {
+            *dst = src.to_ascii_uppercase();
+        }
+        Ok(Self { code })
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Currency({})", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing() {
+        let usd: Currency = "usd".parse().unwrap();
+        assert_eq!(usd, Currency::default());
+        assert_eq!(usd.to_string(), "USD");
+        assert_eq!("USDC1234".parse::<Currency>().unwrap().as_str(), "USDC1234");
+        assert!("".parse::<Currency>().is_err());
+        assert!("USDC12345".parse::<Currency>().is_err());
+        assert!("US D".parse::<Currency>().is_err());
+        assert!("€".parse::<Currency>().is_err());
+    }
+
+    #[test]
+    fn ordering() {
+        let mut codes: Vec<Currency> = ["USD", "BTC", "BTCX", "ETH"]
+            .iter()
+            .map(|code| code.parse().unwrap())
+            .collect();
+        codes.sort();
+        let codes: Vec<_> = codes.iter().map(Currency::as_str).collect();
+        assert_eq!(codes, ["BTC", "BTCX", "ETH", "USD"]);
+    }
+}
@@ -0,0 +1,1262 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::amount::ParseAmountError;
+use crate::client::{Client, ClientEntry, ClientTransaction};
+use crate::config::DuplicatePolicy;
+use crate::fee::FeeKind;
+use crate::ledger::Ledger;
+use crate::sort::SortOrder;
+use crate::{
+    Amount, ClientId, Config, Currency, Error, ErrorKind, FeeEntry, Outcome, Record, RejectReason,
+    Transaction, TransactionId, TransactionState,
+};
+
+/// In-memory store of all client accounts.
+///
+/// Transactions are applied in the order they are added. Accounts are created on the
+/// first transaction that refers to them. Every change to their funds is posted to a
+/// double-entry [`Ledger`], which client balances are projections of.
+#[derive(Default)]
+pub struct Database {
+    config: Config,
+    clients: HashMap<ClientId, Client>,
+    /// Deposits, withdrawals, transfers and unlocks that took effect, by their globally
+    /// unique id. Also serves as the index of which client owns a transaction.
+    transactions: HashMap<TransactionId, Transaction>,
+    audit_log: Vec<AuditEntry>,
+    /// Fees charged and refunded, in the order they took effect.
+    fees: Vec<FeeEntry>,
+    ledger: Ledger,
+}
+
+/// Record of an operator action, such as unlocking an account.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub client: ClientId,
+    pub tx: TransactionId,
+    pub reason: String,
+}
+
+impl Database {
+    pub fn new() -> Self {
+        Self::with_config(Config::default())
+    }
+
+    pub fn with_config(config: Config) -> Self {
+        Self {
+            config,
+            clients: HashMap::new(),
+            transactions: HashMap::new(),
+            audit_log: Vec::new(),
+            fees: Vec::new(),
+            ledger: Ledger::new(),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Validates a record like [`Transaction::from_record_with_config`] does, with the
+    /// config of the database.
+    ///
+    /// A partial amount with more decimal places than any currency can have is reported
+    /// against the scale of the currency of the disputed transaction.
+    pub fn transaction_from_record(&self, record: Record) -> Result<Transaction, Error> {
+        let partial = matches!(record.r#type.as_str(), "dispute" | "resolve" | "chargeback");
+        let (client, id) = (record.client, record.tx);
+        Transaction::from_record_with_config(record, &self.config).map_err(|err| {
+            match (err.kind(), self.currency_of(id)) {
+                (ErrorKind::Amount(ParseAmountError::TooManyDecimalPlaces(_)), Some(currency))
+                    if partial =>
+                {
+                    let scale = self.config.precision.scale_for(currency);
+                    Error::new(ErrorKind::Amount(ParseAmountError::TooManyDecimalPlaces(
+                        scale,
+                    )))
+                    .with_transaction(client, id)
+                }
+                _ => err,
+            }
+        })
+    }
+
+    /// Applies a transaction to the client it refers to.
+    ///
+    /// Transactions that are not valid for the current state of the account, such as
+    /// withdrawals over the available funds or disputes of unknown transactions, leave the
+    /// account unchanged and are reported as [`Outcome::Rejected`]. An error is returned
+    /// only when the transaction itself is malformed or would overflow the balance, in which
+    /// case the database is left unchanged as well.
+    ///
+    /// A deposit, withdrawal, transfer or unlock reusing the id of one that took effect
+    /// earlier is handled according to [`Config::duplicates`]. Disputes, resolves and
+    /// chargebacks must name the client that owns the transaction they refer to, transfers
+    /// and unlocks cannot be disputed. A transfer either takes effect on both clients or on
+    /// none of them. Whether a locked account accepts a transaction is decided by
+    /// [`Config::lock`].
+    ///
+    /// Disputes, resolves and chargebacks may name the part of the transaction they apply
+    /// to. Several disputes of parts of a transaction can be open at the same time.
+    ///
+    /// Withdrawals and chargebacks are charged the fees of [`Config::fees`], which are kept
+    /// as [fee entries](Database::fees) of the transaction. Charging back a withdrawal in
+    /// full refunds its fee.
+    pub fn add_transaction(&mut self, tx: Transaction) -> Result<Outcome, Error> {
+        let tx = self.scale_partial(tx)?;
+        let client_id = tx.client();
+        let new = !self.clients.contains_key(&client_id);
+        let result = self.apply_transaction(tx);
+        if new && result.is_err() {
+            self.clients.remove(&client_id);
+        }
+        result
+    }
+
+    /// Applies a transaction like [`add_transaction`](Database::add_transaction) does,
+    /// creating the client if needed.
+    fn apply_transaction(&mut self, tx: Transaction) -> Result<Outcome, Error> {
+        let (client_id, client_tx) = match &tx {
+            Transaction::Deposit(client_id, tx_id, amount, currency) => (
+                *client_id,
+                ClientTransaction::deposit(*tx_id, *amount).with_currency(*currency),
+            ),
+            Transaction::Withdrawal(client_id, tx_id, amount, currency) => (
+                *client_id,
+                ClientTransaction::withdrawal(*tx_id, *amount).with_currency(*currency),
+            ),
+            Transaction::Dispute(client_id, tx_id, amount) => (
+                *client_id,
+                ClientTransaction::dispute(*tx_id).with_amount(*amount),
+            ),
+            Transaction::Resolve(client_id, tx_id, amount) => (
+                *client_id,
+                ClientTransaction::resolve(*tx_id).with_amount(*amount),
+            ),
+            Transaction::Chargeback(client_id, tx_id, amount) => (
+                *client_id,
+                ClientTransaction::chargeback(*tx_id).with_amount(*amount),
+            ),
+            Transaction::Unlock(client_id, tx_id, _) => {
+                (*client_id, ClientTransaction::unlock(*tx_id))
+            }
+            Transaction::Transfer(client_id, tx_id, _, amount, currency) => (
+                *client_id,
+                ClientTransaction::transfer_out(*tx_id, *amount).with_currency(*currency),
+            ),
+        };
+
+        self.clients
+            .entry(client_id)
+            .or_insert_with(|| Client::new(client_id));
+
+        let registers_id = matches!(
+            tx,
+            Transaction::Deposit(..)
+                | Transaction::Withdrawal(..)
+                | Transaction::Transfer(..)
+                | Transaction::Unlock(..)
+        );
+        let disputes = matches!(
+            tx,
+            Transaction::Dispute(..) | Transaction::Resolve(..) | Transaction::Chargeback(..)
+        );
+        if let Some(original) = self.transactions.get(&tx.tx()) {
+            if registers_id {
+                return Ok(match self.config.duplicates {
+                    DuplicatePolicy::Deduplicate if *original == tx => Outcome::Duplicate,
+                    _ => Outcome::Rejected(RejectReason::DuplicateTxId),
+                });
+            }
+            if disputes
+                && matches!(
+                    original,
+                    Transaction::Transfer(..) | Transaction::Unlock(..)
+                )
+            {
+                return Ok(Outcome::Rejected(RejectReason::NotDisputable));
+            }
+            if original.client() != client_id {
+                return Ok(Outcome::Rejected(RejectReason::ClientMismatch));
+            }
+        }
+        if let Transaction::Transfer(from, _, to, ..) = tx {
+            if from == to {
+                return Ok(Outcome::Rejected(RejectReason::SelfTransfer));
+            }
+        }
+
+        let tx_id = client_tx.id();
+        let context = |kind| Error::new(kind).with_transaction(client_id, tx_id);
+        let fees = self.fees_for(&tx).map_err(context)?;
+        let client = self.clients.get_mut(&client_id).expect("created above");
+        let mut outcome = client
+            .add_transaction(
+                client_tx.with_fees(fees.iter().map(|entry| entry.amount).collect()),
+                &self.config,
+                &mut self.ledger,
+            )
+            .map_err(context)?;
+        if let (Outcome::Applied, Transaction::Transfer(from, _, to, amount, currency)) =
+            (outcome, &tx)
+        {
+            outcome = self.complete_transfer(*from, tx_id, *to, *amount, *currency)?;
+        }
+        if outcome == Outcome::Applied {
+            if let Transaction::Unlock(client, tx, reason) = &tx {
+                self.audit_log.push(AuditEntry {
+                    client: *client,
+                    tx: *tx,
+                    reason: reason.clone(),
+                });
+            }
+            if registers_id {
+                self.transactions.insert(tx_id, tx);
+            }
+            self.fees.extend(fees);
+        }
+        Ok(outcome)
+    }
+
+    /// Brings the amount of a partial dispute, resolve or chargeback, which is parsed at the
+    /// scale it is written with, to the scale of the disputed transaction's currency.
+    fn scale_partial(&self, tx: Transaction) -> Result<Transaction, Error> {
+        let (Transaction::Dispute(_, id, Some(amount))
+        | Transaction::Resolve(_, id, Some(amount))
+        | Transaction::Chargeback(_, id, Some(amount))) = tx
+        else {
+            return Ok(tx);
+        };
+        let Some(currency) = self.currency_of(id) else {
+            return Ok(tx);
+        };
+        let amount = self
+            .config
+            .precision
+            .rescale(amount, currency)
+            .map_err(|err| Error::new(ErrorKind::Amount(err)).with_transaction(tx.client(), id))?;
+        let amount = Some(amount);
+        Ok(match tx {
+            Transaction::Dispute(client, id, _) => Transaction::Dispute(client, id, amount),
+            Transaction::Resolve(client, id, _) => Transaction::Resolve(client, id, amount),
+            Transaction::Chargeback(client, id, _) => Transaction::Chargeback(client, id, amount),
+            _ => unreachable!("matched above"),
+        })
+    }
+
+    /// Returns the currency of a deposit or withdrawal that took effect.
+    fn currency_of(&self, id: TransactionId) -> Option<Currency> {
+        match self.transactions.get(&id)? {
+            Transaction::Deposit(.., currency) | Transaction::Withdrawal(.., currency) => {
+                Some(*currency)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the fees to charge and refund if `tx` takes effect.
+    fn fees_for(&self, tx: &Transaction) -> Result<Vec<FeeEntry>, ErrorKind> {
+        let schedule = &self.config.fees;
+        let charge = |kind, client, tx, amount, currency| -> Result<_, ErrorKind> {
+            let fee = match schedule.fee(kind) {
+                Some(fee) => fee.charge(amount).ok_or(ErrorKind::Overflow)?,
+                None => return Ok(None),
+            };
+            if fee == Amount::new(0) {
+                return Ok(None);
+            }
+            Ok(Some(FeeEntry {
+                client,
+                tx,
+                kind,
+                amount: fee,
+                currency,
+            }))
+        };
+        let mut fees = Vec::new();
+        match *tx {
+            Transaction::Withdrawal(client, tx, amount, currency) => {
+                fees.extend(charge(FeeKind::Withdrawal, client, tx, amount, currency)?);
+            }
+            Transaction::Chargeback(client, tx, amount) => {
+                let (original, currency) = match self.transactions.get(&tx) {
+                    Some(Transaction::Deposit(_, _, amount, currency))
+                    | Some(Transaction::Withdrawal(_, _, amount, currency)) => (*amount, *currency),
+                    _ => return Ok(fees),
+                };
+                let owner = &self.clients[&client];
+                let disputed = owner.disputed_amount(tx).unwrap_or(Amount::new(0));
+                let charged_back = owner.charged_back_amount(tx).unwrap_or(Amount::new(0));
+                let amount = amount.unwrap_or(disputed);
+                // Once the withdrawal is reversed in full, so is its fee.
+                let reversed = charged_back.checked_add(amount) == Some(original);
+                for entry in &self.fees {
+                    if reversed && entry.tx == tx && entry.kind == FeeKind::Withdrawal {
+                        fees.push(FeeEntry {
+                            amount: Amount::new(0)
+                                .checked_sub(entry.amount)
+                                .ok_or(ErrorKind::Overflow)?,
+                            ..*entry
+                        });
+                    }
+                }
+                fees.extend(charge(FeeKind::Chargeback, client, tx, amount, currency)?);
+            }
+            _ => {}
+        }
+        Ok(fees)
+    }
+
+    /// Credits the receiving side of a transfer the sender can be debited for, posting both
+    /// sides in a single journal entry. If the receiver does not accept the transfer,
+    /// nothing is posted, and a receiver that is new to the database is not added.
+    fn complete_transfer(
+        &mut self,
+        from: ClientId,
+        tx_id: TransactionId,
+        to: ClientId,
+        amount: Amount,
+        currency: Currency,
+    ) -> Result<Outcome, Error> {
+        let client_tx = ClientTransaction::transfer_in(tx_id, amount, from).with_currency(currency);
+        let new = !self.clients.contains_key(&to);
+        let receiver = self.clients.entry(to).or_insert_with(|| Client::new(to));
+        let outcome = receiver
+            .add_transaction(client_tx, &self.config, &mut self.ledger)
+            .map_err(|kind| Error::new(kind).with_transaction(to, tx_id));
+        if new && !matches!(outcome, Ok(Outcome::Applied)) {
+            self.clients.remove(&to);
+        }
+        outcome
+    }
+
+    /// Returns the sum of the total funds of all clients in `currency`, or `None` if it
+    /// does not fit into an [`Amount`].
+    ///
+    /// Only deposits, withdrawals, chargebacks and fees change it, transfers between clients
+    /// leave it as it is.
+    pub fn system_total(&self, currency: Currency) -> Option<Amount> {
+        self.entries()
+            .filter(|entry| entry.currency() == currency)
+            .try_fold(Amount::new(0), |sum, entry| sum.checked_add(entry.total()))
+    }
+
+    /// Returns the current balance of a client in the default currency, if any transaction
+    /// referred to it.
+    pub fn get_entry(&self, client_id: ClientId) -> Option<ClientEntry> {
+        self.get_entry_in(client_id, self.config.currency)
+    }
+
+    /// Returns the current balance of a client in `currency`, if any transaction referred
+    /// to the client.
+    pub fn get_entry_in(&self, client_id: ClientId, currency: Currency) -> Option<ClientEntry> {
+        self.clients
+            .get(&client_id)
+            .map(|client| client.get_entry(&self.ledger, currency))
+    }
+
+    /// Returns the dispute state of a deposit or withdrawal that took effect.
+    pub fn transaction_state(&self, tx_id: TransactionId) -> Option<TransactionState> {
+        let owner = self.transactions.get(&tx_id)?.client();
+        self.clients.get(&owner)?.transaction_state(tx_id)
+    }
+
+    /// Returns the part of a deposit or withdrawal that is under open disputes.
+    pub fn disputed_amount(&self, tx_id: TransactionId) -> Option<Amount> {
+        let owner = self.transactions.get(&tx_id)?.client();
+        self.clients.get(&owner)?.disputed_amount(tx_id)
+    }
+
+    /// Returns the operator actions that took effect, in the order they were applied.
+    pub fn audit_log(&self) -> &[AuditEntry] {
+        &self.audit_log
+    }
+
+    /// Returns the fees charged and refunded, in the order they were applied.
+    pub fn fees(&self) -> &[FeeEntry] {
+        &self.fees
+    }
+
+    /// Returns the ledger all changes to the funds of clients are posted to.
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    /// Returns the current balances of all clients, one per client and currency the client
+    /// holds, in no particular order of clients.
+    ///
+    /// Clients that never held any funds are listed with a zero balance in the default
+    /// currency.
+    pub fn entries(&self) -> impl Iterator<Item = ClientEntry> + '_ {
+        self.clients
+            .values()
+            .flat_map(|client| client.entries(&self.ledger, self.config.currency))
+    }
+
+    /// Returns the current balances of all clients like [`entries`](Database::entries)
+    /// does, sorted in `order`.
+    pub fn sorted_entries(&self, order: &SortOrder) -> Vec<ClientEntry> {
+        let mut entries: Vec<_> = self.entries().collect();
+        order.sort(&mut entries);
+        entries
+    }
+
+    /// Returns the balances of clients whose available funds are below zero, in no
+    /// particular order.
+    ///
+    /// Such accounts carry negative exposure, typically after a deposit was disputed when
+    /// part of it had already been withdrawn.
+    pub fn negative_exposure(&self) -> impl Iterator<Item = ClientEntry> + '_ {
+        self.entries()
+            .filter(|entry| entry.available() < Amount::new(0))
+    }
+
+    /// Returns the balances of clients in another currency than the default one, in no
+    /// particular order.
+    ///
+    /// CSV output can only hold them with a currency column.
+    pub fn foreign_balances(&self) -> impl Iterator<Item = ClientEntry> + '_ {
+        self.entries()
+            .filter(|entry| entry.currency() != self.config.currency)
+    }
+
+    /// Writes the balances of all clients as CSV, including the header row, ordered by
+    /// client and currency.
+    ///
+    /// The output has no currency column, so all balances must be in the default currency,
+    /// otherwise nothing is written and an [`io::ErrorKind::InvalidData`] error is returned.
+    pub fn write_all(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.write_csv(writer, b',', &SortOrder::default(), false)
+    }
+
+    /// Writes the balances of all clients like [`write_all`](Database::write_all) does, with
+    /// fields separated by `delimiter` and rows sorted in `order`.
+    ///
+    /// With `with_currency` a `currency` column follows the client and balances may be in
+    /// any currency. The columns never depend on the balances, so the output of different
+    /// runs can be read the same way.
+    pub fn write_csv(
+        &self,
+        writer: &mut dyn Write,
+        delimiter: u8,
+        order: &SortOrder,
+        with_currency: bool,
+    ) -> io::Result<()> {
+        let separator = char::from(delimiter).to_string();
+        let entries = self.sorted_entries(order);
+        let default = self.config.currency;
+        if let Some(entry) = entries
+            .iter()
+            .find(|entry| !with_currency && entry.currency() != default)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "balance of client {} in {} needs a currency column",
+                    entry.id(),
+                    entry.currency()
+                ),
+            ));
+        }
+        let mut header = vec!["client", "available", "held", "total", "locked"];
+        if with_currency {
+            header.insert(1, "currency");
+        }
+        writeln!(writer, "{}", header.join(&separator))?;
+        for entry in entries {
+            let mut fields = vec![
+                entry.id().to_string(),
+                entry.available().to_string(),
+                entry.held().to_string(),
+                entry.total().to_string(),
+                entry.locked().to_string(),
+            ];
+            if with_currency {
+                fields.insert(1, entry.currency().to_string());
+            }
+            writeln!(writer, "{}", fields.join(&separator))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the balances of all clients as JSON Lines, one object per client and currency,
+    /// sorted in `order`.
+    ///
+    /// Amounts are written as strings, so that readers keep their exact decimal value.
+    pub fn write_jsonl(&self, writer: &mut dyn Write, order: &SortOrder) -> io::Result<()> {
+        for entry in self.sorted_entries(order) {
+            writeln!(
+                writer,
+                r#"{{"client":{},"currency":"{}","available":"{}","held":"{}","total":"{}","locked":{}}}"#,
+                entry.id(),
+                entry.currency(),
+                entry.available(),
+                entry.held(),
+                entry.total(),
+                entry.locked()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::{ExcessPrecision, RoundingMode, UnderlyingAmountType, MAX_SCALE};
+    use crate::config::{LockPolicy, OverdraftMode, OverdraftPolicy, Precision};
+    use crate::fee::Fee;
+    use crate::ledger::Account;
+    use crate::ErrorKind;
+    use crate::FeeSchedule;
+    use crate::Record;
+    use crate::RejectReason;
+
+    #[test]
+    fn creates_clients_on_first_transaction() {
+        let mut database = Database::new();
+        assert!(database.get_entry(1).is_none());
+
+        assert_eq!(
+            database
+                .add_transaction(Transaction::Deposit(
+                    1,
+                    1,
+                    Amount::new(10000),
+                    Currency::default()
+                ))
+                .unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            database
+                .add_transaction(Transaction::Dispute(2, 7, None))
+                .unwrap(),
+            Outcome::Rejected(RejectReason::UnknownTx)
+        );
+
+        assert_eq!(database.get_entry(1).unwrap().to_string(), "1,1,0,1,false");
+        assert_eq!(database.get_entry(2).unwrap().to_string(), "2,0,0,0,false");
+        assert_eq!(database.entries().count(), 2);
+    }
+
+    #[test]
+    fn rejects_duplicate_ids_across_clients() {
+        let mut database = Database::new();
+        let deposit = Transaction::Deposit(1, 1, Amount::new(10000), Currency::default());
+        assert_eq!(
+            database.add_transaction(deposit.clone()).unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            database.add_transaction(deposit.clone()).unwrap(),
+            Outcome::Rejected(RejectReason::DuplicateTxId)
+        );
+        assert_eq!(
+            database
+                .add_transaction(Transaction::Withdrawal(
+                    2,
+                    1,
+                    Amount::new(10000),
+                    Currency::default()
+                ))
+                .unwrap(),
+            Outcome::Rejected(RejectReason::DuplicateTxId)
+        );
+        assert_eq!(database.get_entry(1).unwrap().to_string(), "1,1,0,1,false");
+    }
+
+    #[test]
+    fn deduplicates_exact_repeats() {
+        let mut database = Database::with_config(Config {
+            duplicates: DuplicatePolicy::Deduplicate,
+            ..Config::default()
+        });
+        let deposit = Transaction::Deposit(1, 1, Amount::new(10000), Currency::default());
+        assert_eq!(
+            database.add_transaction(deposit.clone()).unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            database.add_transaction(deposit).unwrap(),
+            Outcome::Duplicate
+        );
+        assert_eq!(
+            database
+                .add_transaction(Transaction::Deposit(
+                    1,
+                    1,
+                    Amount::new(20000),
+                    Currency::default()
+                ))
+                .unwrap(),
+            Outcome::Rejected(RejectReason::DuplicateTxId)
+        );
+        assert_eq!(database.get_entry(1).unwrap().to_string(), "1,1,0,1,false");
+    }
+
+    #[test]
+    fn rejected_transactions_do_not_take_the_id() {
+        let mut database = Database::new();
+        assert_eq!(
+            database
+                .add_transaction(Transaction::Withdrawal(
+                    1,
+                    1,
+                    Amount::new(10000),
+                    Currency::default()
+                ))
+                .unwrap(),
+            Outcome::Rejected(RejectReason::InsufficientFunds)
+        );
+        assert_eq!(
+            database
+                .add_transaction(Transaction::Deposit(
+                    1,
+                    1,
+                    Amount::new(10000),
+                    Currency::default()
+                ))
+                .unwrap(),
+            Outcome::Applied
+        );
+    }
+
+    #[test]
+    fn rejects_disputes_naming_another_client() {
+        let mut database = Database::new();
+        database
+            .add_transaction(Transaction::Deposit(
+                1,
+                1,
+                Amount::new(10000),
+                Currency::default(),
+            ))
+            .unwrap();
+        database
+            .add_transaction(Transaction::Deposit(
+                2,
+                2,
+                Amount::new(10000),
+                Currency::default(),
+            ))
+            .unwrap();
+
+        for tx in [
+            Transaction::Dispute(2, 1, None),
+            Transaction::Resolve(2, 1, None),
+            Transaction::Chargeback(2, 1, None),
+        ] {
+            assert_eq!(
+                database.add_transaction(tx).unwrap(),
+                Outcome::Rejected(RejectReason::ClientMismatch)
+            );
+        }
+        assert_eq!(database.get_entry(1).unwrap().to_string(), "1,1,0,1,false");
+        assert_eq!(database.get_entry(2).unwrap().to_string(), "2,1,0,1,false");
+
+        assert_eq!(
+            database
+                .add_transaction(Transaction::Dispute(1, 1, None))
+                .unwrap(),
+            Outcome::Applied
+        );
+    }
+
+    #[test]
+    fn records_unlocks_in_audit_log() {
+        let mut database = Database::new();
+        for tx in [
+            Transaction::Deposit(1, 1, Amount::new(10000), Currency::default()),
+            Transaction::Dispute(1, 1, None),
+            Transaction::Chargeback(1, 1, None),
+        ] {
+            database.add_transaction(tx).unwrap();
+        }
+        let unlock = Transaction::Unlock(1, 2, String::from("reviewed"));
+        assert_eq!(
+            database.add_transaction(unlock.clone()).unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            database
+                .add_transaction(Transaction::Unlock(1, 3, String::from("again")))
+                .unwrap(),
+            Outcome::Rejected(RejectReason::NotLocked)
+        );
+        assert_eq!(
+            database.audit_log(),
+            [AuditEntry {
+                client: 1,
+                tx: 2,
+                reason: String::from("reviewed"),
+            }]
+        );
+        assert!(!database.get_entry(1).unwrap().locked());
+
+        // Unlock ids are unique like any other and cannot be disputed.
+        for (tx, reason) in [
+            (unlock, RejectReason::DuplicateTxId),
+            (
+                Transaction::Unlock(2, 1, String::from("reviewed")),
+                RejectReason::DuplicateTxId,
+            ),
+            (
+                Transaction::Deposit(1, 2, Amount::new(10000), Currency::default()),
+                RejectReason::DuplicateTxId,
+            ),
+            (
+                Transaction::Dispute(1, 2, None),
+                RejectReason::NotDisputable,
+            ),
+        ] {
+            assert_eq!(
+                database.add_transaction(tx).unwrap(),
+                Outcome::Rejected(reason)
+            );
+        }
+    }
+
+    #[test]
+    fn reports_negative_exposure() {
+        let mut database = Database::with_config(Config {
+            overdraft: OverdraftPolicy {
+                limit: Some(Amount::new(10000)),
+                client_limits: HashMap::from([(2, Amount::new(0))]),
+                mode: OverdraftMode::PartialHold,
+            },
+            ..Config::default()
+        });
+        for tx in [
+            Transaction::Deposit(1, 1, Amount::new(30000), Currency::default()),
+            Transaction::Withdrawal(1, 2, Amount::new(20000), Currency::default()),
+            Transaction::Deposit(2, 3, Amount::new(30000), Currency::default()),
+            Transaction::Withdrawal(2, 4, Amount::new(20000), Currency::default()),
+        ] {
+            database.add_transaction(tx).unwrap();
+        }
+        assert_eq!(
+            database
+                .add_transaction(Transaction::Dispute(1, 1, None))
+                .unwrap(),
+            Outcome::PartiallyHeld(Amount::new(20000))
+        );
+        assert_eq!(
+            database
+                .add_transaction(Transaction::Dispute(2, 3, None))
+                .unwrap(),
+            Outcome::PartiallyHeld(Amount::new(10000))
+        );
+
+        let exposed: Vec<_> = database
+            .negative_exposure()
+            .map(|entry| entry.to_string())
+            .collect();
+        assert_eq!(exposed, ["1,-1,2,1,false"]);
+    }
+
+    #[test]
+    fn scales_partial_amounts_by_the_disputed_currency() {
+        let btc: Currency = "BTC".parse().unwrap();
+        let config = |excess| Config {
+            precision: Precision {
+                scales: HashMap::from([(btc, 8)]),
+                excess,
+                ..Precision::default()
+            },
+            ..Config::default()
+        };
+        let row = |r#type: &str, tx, amount: &str, currency: Option<&str>| Record {
+            r#type: String::from(r#type),
+            client: 1,
+            tx,
+            amount: Some(String::from(amount)),
+            currency: currency.map(String::from),
+            to_client: None,
+            reason: None,
+            seq: None,
+        };
+        let add = |database: &mut Database, record| {
+            let tx = database.transaction_from_record(record)?;
+            database.add_transaction(tx)
+        };
+
+        let mut database = Database::with_config(config(ExcessPrecision::Reject));
+        add(&mut database, row("deposit", 1, "1", Some("BTC"))).unwrap();
+        assert_eq!(
+            add(&mut database, row("dispute", 1, "0.00000001", None)).unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            database.get_entry_in(1, btc).unwrap().to_string(),
+            "1,0.99999999,0.00000001,1,false"
+        );
+        let err = add(&mut database, row("dispute", 1, "0.000000001", None)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "client 1, tx 1: invalid amount: amount has more than 8 decimal places"
+        );
+        // So are digits beyond those any amount can have.
+        let digits = format!("0.{}1", "0".repeat(MAX_SCALE as usize));
+        let err = add(&mut database, row("dispute", 1, &digits, None)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "client 1, tx 1: invalid amount: amount has more than 8 decimal places"
+        );
+
+        let mut database =
+            Database::with_config(config(ExcessPrecision::Round(RoundingMode::HalfEven)));
+        add(&mut database, row("deposit", 1, "1", Some("BTC"))).unwrap();
+        assert_eq!(
+            add(&mut database, row("dispute", 1, "0.000000015", None)).unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            add(&mut database, row("resolve", 1, "0.00000001", None)).unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            database.get_entry_in(1, btc).unwrap().to_string(),
+            "1,0.99999999,0.00000001,1,false"
+        );
+    }
+
+    #[test]
+    fn writes_currency_column_on_request() {
+        let mut database = Database::new();
+        database
+            .add_transaction(Transaction::Deposit(
+                1,
+                1,
+                Amount::new(10000),
+                Currency::default(),
+            ))
+            .unwrap();
+        let mut output = Vec::new();
+        database.write_all(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n1,1,0,1,false\n"
+        );
+        let mut output = Vec::new();
+        database
+            .write_csv(&mut output, b',', &SortOrder::default(), true)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,currency,available,held,total,locked\n1,USD,1,0,1,false\n"
+        );
+        assert_eq!(database.foreign_balances().count(), 0);
+
+        let jpy: Currency = "JPY".parse().unwrap();
+        database
+            .add_transaction(Transaction::Deposit(1, 2, Amount::with_scale(500, 0), jpy))
+            .unwrap();
+        let foreign: Vec<_> = database.foreign_balances().collect();
+        assert_eq!(foreign.len(), 1);
+        assert_eq!(foreign[0].currency(), jpy);
+        let mut output = Vec::new();
+        let err = database.write_all(&mut output).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "balance of client 1 in JPY needs a currency column"
+        );
+        assert!(output.is_empty());
+        database
+            .write_csv(&mut output, b',', &SortOrder::default(), true)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,currency,available,held,total,locked\n1,JPY,500,0,500,false\n1,USD,1,0,1,false\n"
+        );
+        assert_eq!(
+            database.get_entry_in(1, jpy).unwrap().to_string(),
+            "1,500,0,500,false"
+        );
+    }
+
+    #[test]
+    fn writes_balances_in_order() {
+        let mut database = Database::new();
+        for client in (1..=50).rev() {
+            let tx = Transaction::Deposit(
+                client,
+                u32::from(client),
+                Amount::new(UnderlyingAmountType::from(client % 3) * 10000),
+                Currency::default(),
+            );
+            database.add_transaction(tx).unwrap();
+        }
+        let mut output = Vec::new();
+        database.write_all(&mut output).unwrap();
+        let expected: String = (1..=50)
+            .map(|client| format!("{},{},0,{},false\n", client, client % 3, client % 3))
+            .collect();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!("client,available,held,total,locked\n{}", expected)
+        );
+
+        let order = "total:desc".parse().unwrap();
+        let mut output = Vec::new();
+        database
+            .write_csv(&mut output, b',', &order, false)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let clients: Vec<&str> = output
+            .lines()
+            .skip(1)
+            .take(3)
+            .map(|line| line.split(',').next().unwrap())
+            .collect();
+        assert_eq!(clients, ["2", "5", "8"]);
+    }
+
+    #[test]
+    fn writes_other_formats() {
+        let mut database = Database::new();
+        database
+            .add_transaction(Transaction::Deposit(
+                1,
+                1,
+                Amount::new(15000),
+                Currency::default(),
+            ))
+            .unwrap();
+        let mut output = Vec::new();
+        database
+            .write_csv(&mut output, b'\t', &SortOrder::default(), false)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client\tavailable\theld\ttotal\tlocked\n1\t1.5\t0\t1.5\tfalse\n"
+        );
+
+        let mut output = Vec::new();
+        database
+            .write_jsonl(&mut output, &SortOrder::default())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"{"client":1,"currency":"USD","available":"1.5","held":"0","total":"1.5","locked":false}"#
+                .to_owned()
+                + "\n"
+        );
+    }
+
+    #[test]
+    fn transfers_between_clients() {
+        let usd = Currency::default();
+        let mut database = Database::new();
+        for tx in [
+            Transaction::Deposit(1, 1, Amount::new(30000), usd),
+            Transaction::Deposit(2, 2, Amount::new(10000), usd),
+        ] {
+            database.add_transaction(tx).unwrap();
+        }
+        let total = database.system_total(usd);
+
+        let transfer = Transaction::Transfer(1, 3, 2, Amount::new(20000), usd);
+        assert_eq!(
+            database.add_transaction(transfer.clone()).unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            database.add_transaction(transfer).unwrap(),
+            Outcome::Rejected(RejectReason::DuplicateTxId)
+        );
+        assert_eq!(database.get_entry(1).unwrap().to_string(), "1,1,0,1,false");
+        assert_eq!(database.get_entry(2).unwrap().to_string(), "2,3,0,3,false");
+        assert_eq!(database.system_total(usd), total);
+
+        for (tx, reason) in [
+            (
+                Transaction::Transfer(1, 4, 2, Amount::new(20000), usd),
+                RejectReason::InsufficientFunds,
+            ),
+            (
+                Transaction::Transfer(1, 5, 1, Amount::new(10000), usd),
+                RejectReason::SelfTransfer,
+            ),
+            (
+                Transaction::Transfer(1, 6, 2, Amount::new(-10000), usd),
+                RejectReason::NegativeAmount,
+            ),
+            (
+                Transaction::Dispute(1, 3, None),
+                RejectReason::NotDisputable,
+            ),
+            (
+                Transaction::Dispute(2, 3, None),
+                RejectReason::NotDisputable,
+            ),
+        ] {
+            assert_eq!(
+                database.add_transaction(tx).unwrap(),
+                Outcome::Rejected(reason)
+            );
+        }
+        assert_eq!(database.system_total(usd), total);
+    }
+
+    #[test]
+    fn posts_to_the_ledger() {
+        let usd = Currency::default();
+        let mut database = Database::with_config(Config {
+            fees: FeeSchedule {
+                withdrawal: Some(Fee {
+                    flat: Amount::new(1000),
+                    ..Fee::default()
+                }),
+                chargeback: Some(Fee {
+                    flat: Amount::new(10000),
+                    ..Fee::default()
+                }),
+            },
+            ..Config::default()
+        });
+        for tx in [
+            Transaction::Deposit(1, 1, Amount::new(100000), usd),
+            Transaction::Withdrawal(1, 2, Amount::new(20000), usd),
+            Transaction::Deposit(2, 3, Amount::new(50000), usd),
+            Transaction::Transfer(2, 4, 1, Amount::new(10000), usd),
+            Transaction::Dispute(1, 2, None),
+            Transaction::Chargeback(1, 2, None),
+        ] {
+            database.add_transaction(tx).unwrap();
+        }
+        // A rejected transaction posts nothing.
+        database
+            .add_transaction(Transaction::Withdrawal(2, 5, Amount::new(100000), usd))
+            .unwrap();
+
+        let ledger = database.ledger();
+        let journal: Vec<_> = ledger
+            .journal()
+            .iter()
+            .map(|entry| {
+                let postings: Vec<_> = entry
+                    .postings
+                    .iter()
+                    .map(|posting| format!("{} {}", posting.account, posting.amount))
+                    .collect();
+                (entry.tx, postings.join(", "))
+            })
+            .collect();
+        // Fees are entries of their own, attributed to the transaction they are charged for,
+        // and the refund of the withdrawal fee reverses its entry.
+        assert_eq!(
+            journal,
+            [
+                (1, "client:1:available 10, system:cash -10"),
+                (2, "client:1:available -2, system:cash 2"),
+                (2, "client:1:available -0.1, system:fees 0.1"),
+                (3, "client:2:available 5, system:cash -5"),
+                (4, "client:1:available 1, client:2:available -1"),
+                (2, "client:1:held 2, system:chargeback_loss -2"),
+                (2, "client:1:available 2, client:1:held -2"),
+                (2, "client:1:available 0.1, system:fees -0.1"),
+                (2, "client:1:available -1, system:fees 1"),
+            ]
+            .map(|(tx, postings)| (tx, String::from(postings)))
+        );
+        assert!(ledger.verify());
+        let balance = |account| ledger.balance(account, usd);
+        assert_eq!(balance(Account::Available(1)), Amount::new(100000));
+        assert_eq!(balance(Account::Held(1)), Amount::new(0));
+        assert_eq!(balance(Account::Available(2)), Amount::new(40000));
+        assert_eq!(balance(Account::Cash), Amount::new(-130000));
+        assert_eq!(balance(Account::ChargebackLoss), Amount::new(-20000));
+        assert_eq!(balance(Account::Fees), Amount::new(10000));
+        assert_eq!(database.get_entry(1).unwrap().to_string(), "1,10,0,10,true");
+    }
+
+    #[test]
+    fn charges_and_refunds_fees() {
+        let usd = Currency::default();
+        let mut database = Database::with_config(Config {
+            fees: FeeSchedule {
+                withdrawal: Some(Fee {
+                    flat: Amount::new(100),
+                    percent: Amount::new(10000),
+                    ..Fee::default()
+                }),
+                chargeback: Some(Fee {
+                    flat: Amount::new(20000),
+                    ..Fee::default()
+                }),
+            },
+            ..Config::default()
+        });
+        database
+            .add_transaction(Transaction::Deposit(1, 1, Amount::new(100000), usd))
+            .unwrap();
+
+        // The fee must be covered along with the withdrawal.
+        assert_eq!(
+            database
+                .add_transaction(Transaction::Withdrawal(1, 2, Amount::new(99500), usd))
+                .unwrap(),
+            Outcome::Rejected(RejectReason::InsufficientFunds)
+        );
+        assert!(database.fees().is_empty());
+        database
+            .add_transaction(Transaction::Withdrawal(1, 3, Amount::new(50000), usd))
+            .unwrap();
+        assert_eq!(
+            database.get_entry(1).unwrap().to_string(),
+            "1,4.94,0,4.94,false"
+        );
+
+        // Charging back the withdrawal refunds its fee and charges the chargeback fee.
+        for tx in [
+            Transaction::Dispute(1, 3, None),
+            Transaction::Chargeback(1, 3, None),
+        ] {
+            database.add_transaction(tx).unwrap();
+        }
+        assert_eq!(database.get_entry(1).unwrap().to_string(), "1,8,0,8,true");
+        let fees: Vec<_> = database
+            .fees()
+            .iter()
+            .map(|entry| (entry.client, entry.tx, entry.kind, entry.amount.to_string()))
+            .collect();
+        assert_eq!(
+            fees,
+            [
+                (1, 3, FeeKind::Withdrawal, String::from("0.06")),
+                (1, 3, FeeKind::Withdrawal, String::from("-0.06")),
+                (1, 3, FeeKind::Chargeback, String::from("2")),
+            ]
+        );
+        assert_eq!(database.system_total(usd), Some(Amount::new(80000)));
+    }
+
+    #[test]
+    fn refunds_fees_of_withdrawals_charged_back_in_full() {
+        let usd = Currency::default();
+        let fee = Fee {
+            flat: Amount::new(1000),
+            ..Fee::default()
+        };
+        let mut database = Database::with_config(Config {
+            fees: FeeSchedule {
+                withdrawal: Some(fee),
+                chargeback: Some(fee),
+            },
+            ..Config::default()
+        });
+        for tx in [
+            Transaction::Deposit(1, 1, Amount::new(100000), usd),
+            Transaction::Withdrawal(1, 2, Amount::new(50000), usd),
+            Transaction::Dispute(1, 2, None),
+            Transaction::Chargeback(1, 2, Some(Amount::new(20000))),
+        ] {
+            database.add_transaction(tx).unwrap();
+        }
+        assert_eq!(database.disputed_amount(2), Some(Amount::new(30000)));
+        assert_eq!(database.fees().len(), 2);
+        assert_eq!(
+            database.get_entry(1).unwrap().to_string(),
+            "1,6.8,3,9.8,true"
+        );
+
+        database
+            .add_transaction(Transaction::Chargeback(1, 2, None))
+            .unwrap();
+        let fees: Vec<_> = database
+            .fees()
+            .iter()
+            .map(|entry| (entry.kind, entry.amount.to_string()))
+            .collect();
+        assert_eq!(
+            fees,
+            [
+                (FeeKind::Withdrawal, String::from("0.1")),
+                (FeeKind::Chargeback, String::from("0.1")),
+                (FeeKind::Withdrawal, String::from("-0.1")),
+                (FeeKind::Chargeback, String::from("0.1")),
+            ]
+        );
+        assert_eq!(
+            database.get_entry(1).unwrap().to_string(),
+            "1,9.8,0,9.8,true"
+        );
+    }
+
+    #[test]
+    fn transfers_are_atomic() {
+        let usd = Currency::default();
+        let mut database = Database::with_config(Config {
+            lock: LockPolicy::frozen(),
+            ..Config::default()
+        });
+        for tx in [
+            Transaction::Deposit(1, 1, Amount::new(10000), usd),
+            Transaction::Deposit(2, 2, Amount::new(10000), usd),
+            Transaction::Dispute(2, 2, None),
+            Transaction::Chargeback(2, 2, None),
+        ] {
+            database.add_transaction(tx).unwrap();
+        }
+
+        // The sender lacks the funds, the receiver is not created.
+        assert_eq!(
+            database
+                .add_transaction(Transaction::Transfer(1, 3, 99, Amount::new(500000), usd))
+                .unwrap(),
+            Outcome::Rejected(RejectReason::InsufficientFunds)
+        );
+        assert!(database.get_entry(99).is_none());
+        assert_eq!(database.entries().count(), 2);
+
+        // The receiver is locked, the sender keeps the funds.
+        assert_eq!(
+            database
+                .add_transaction(Transaction::Transfer(1, 3, 2, Amount::new(5000), usd))
+                .unwrap(),
+            Outcome::Rejected(RejectReason::AccountLocked)
+        );
+        assert_eq!(database.get_entry(1).unwrap().to_string(), "1,1,0,1,false");
+        assert_eq!(database.get_entry(2).unwrap().to_string(), "2,0,0,0,true");
+
+        // So does a locked sender.
+        assert_eq!(
+            database
+                .add_transaction(Transaction::Transfer(2, 4, 1, Amount::new(5000), usd))
+                .unwrap(),
+            Outcome::Rejected(RejectReason::AccountLocked)
+        );
+
+        // The receiver's balance would overflow. All funds of clients come from
+        // `system:cash`, which leaves room for a deposit of just below the maximum.
+        let deposit = Amount::new(UnderlyingAmountType::MAX - 9999);
+        database
+            .add_transaction(Transaction::Deposit(3, 5, deposit, usd))
+            .unwrap();
+        let err = database
+            .add_transaction(Transaction::Transfer(1, 6, 3, Amount::new(10000), usd))
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Overflow));
+        assert_eq!(database.get_entry(1).unwrap().to_string(), "1,1,0,1,false");
+        assert_eq!(database.entries().count(), 3);
+
+        // Neither does a failed first transaction of a client leave the client behind.
+        let err = database
+            .add_transaction(Transaction::Deposit(4, 7, Amount::new(10000), usd))
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Overflow));
+        assert!(database.get_entry(4).is_none());
+        assert_eq!(database.entries().count(), 3);
+
+        // Failed transfers do not take the id.
+        assert_eq!(
+            database
+                .add_transaction(Transaction::Withdrawal(1, 6, Amount::new(5000), usd))
+                .unwrap(),
+            Outcome::Applied
+        );
+    }
+}
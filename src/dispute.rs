@@ -0,0 +1,147 @@
+use std::fmt;
+
+use crate::{Amount, RejectReason};
+
+/// Where a deposit or withdrawal is in its dispute lifecycle.
+///
+/// A transaction starts as [`Processed`](TransactionState::Processed). A dispute moves it to
+/// [`Disputed`](TransactionState::Disputed), from where a resolve moves it to
+/// [`Resolved`](TransactionState::Resolved) and a chargeback to the final
+/// [`ChargedBack`](TransactionState::ChargedBack). Whether funds of a resolved transaction
+/// can be disputed again is decided by [`RedisputePolicy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TransactionState {
+    Processed,
+    Disputed,
+    Resolved,
+    ChargedBack,
+}
+
+impl fmt::Display for TransactionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            TransactionState::Processed => "processed",
+            TransactionState::Disputed => "disputed",
+            TransactionState::Resolved => "resolved",
+            TransactionState::ChargedBack => "charged back",
+        };
+        write!(f, "{}", state)
+    }
+}
+
+/// Whether funds of a transaction can be disputed again after an earlier dispute of them was
+/// resolved.
+///
+/// Funds of a transaction that were never disputed can always be disputed, for example the
+/// rest of a deposit after a dispute of a part of it was resolved or charged back.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RedisputePolicy {
+    /// Funds can be disputed only once.
+    #[default]
+    Never,
+    /// Resolved funds can be disputed again, as long as they were disputed fewer than the
+    /// given number of times in total.
+    UpTo(u32),
+}
+
+impl RedisputePolicy {
+    /// Whether funds that were disputed `disputes` times can be disputed again.
+    pub fn allows(&self, disputes: u32) -> bool {
+        match *self {
+            RedisputePolicy::Never => disputes == 0,
+            RedisputePolicy::UpTo(max) => disputes < max,
+        }
+    }
+}
+
+/// Change to a client's balances.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BalanceChange {
+    pub available: Amount,
+    pub held: Amount,
+}
+
+impl BalanceChange {
+    pub fn new(available: Amount, held: Amount) -> Self {
+        Self { available, held }
+    }
+
+    pub fn none() -> Self {
+        Self::new(Amount::new(0), Amount::new(0))
+    }
+}
+
+/// Defines what disputing a withdrawal does to the client's balances.
+///
+/// Disputed deposits always move their amount from available to held funds. Withdrawals are
+/// treated differently by different partners, so their handling is pluggable. The policy is
+/// selected through [`Config::dispute_policy`](crate::Config::dispute_policy).
+pub trait DisputePolicy: fmt::Debug + Send + Sync {
+    /// Change applied when a withdrawal of `amount` is disputed, or the reason the dispute
+    /// is rejected.
+    fn dispute_withdrawal(&self, amount: Amount) -> Result<BalanceChange, RejectReason>;
+
+    /// Change applied when the dispute of a withdrawal of `amount` is resolved.
+    fn resolve_withdrawal(&self, amount: Amount) -> BalanceChange;
+
+    /// Change applied when a disputed withdrawal of `amount` is charged back.
+    fn chargeback_withdrawal(&self, amount: Amount) -> BalanceChange;
+}
+
+/// Withdrawals cannot be disputed.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RejectWithdrawalDisputes;
+
+impl DisputePolicy for RejectWithdrawalDisputes {
+    fn dispute_withdrawal(&self, _amount: Amount) -> Result<BalanceChange, RejectReason> {
+        Err(RejectReason::NotDisputable)
+    }
+
+    fn resolve_withdrawal(&self, _amount: Amount) -> BalanceChange {
+        BalanceChange::none()
+    }
+
+    fn chargeback_withdrawal(&self, _amount: Amount) -> BalanceChange {
+        BalanceChange::none()
+    }
+}
+
+/// A disputed withdrawal is credited back to the client as held funds, which cannot be
+/// withdrawn until the dispute ends, so held funds never turn negative. A chargeback
+/// releases the credit to the available funds, a resolve takes it back.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HoldWithdrawalAsCredit;
+
+impl DisputePolicy for HoldWithdrawalAsCredit {
+    fn dispute_withdrawal(&self, amount: Amount) -> Result<BalanceChange, RejectReason> {
+        Ok(BalanceChange::new(Amount::new(0), amount))
+    }
+
+    fn resolve_withdrawal(&self, amount: Amount) -> BalanceChange {
+        BalanceChange::new(Amount::new(0), Amount::new(0) - amount)
+    }
+
+    fn chargeback_withdrawal(&self, amount: Amount) -> BalanceChange {
+        BalanceChange::new(amount, Amount::new(0) - amount)
+    }
+}
+
+/// A disputed withdrawal is reversed outright by crediting it back to the available funds.
+/// A chargeback makes the reversal final, a resolve debits the amount again even if that
+/// leaves the available funds negative.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReverseWithdrawal;
+
+impl DisputePolicy for ReverseWithdrawal {
+    fn dispute_withdrawal(&self, amount: Amount) -> Result<BalanceChange, RejectReason> {
+        Ok(BalanceChange::new(amount, Amount::new(0)))
+    }
+
+    fn resolve_withdrawal(&self, amount: Amount) -> BalanceChange {
+        BalanceChange::new(Amount::new(0) - amount, Amount::new(0))
+    }
+
+    fn chargeback_withdrawal(&self, _amount: Amount) -> BalanceChange {
+        BalanceChange::none()
+    }
+}
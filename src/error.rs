@@ -0,0 +1,208 @@
+use std::{fmt, io};
+
+use crate::amount::ParseAmountError;
+use crate::{ClientId, TransactionId};
+
+/// Error raised while reading or processing a single input row.
+///
+/// Carries as much context as was known at the point of failure, so that callers
+/// can decide whether to skip the row, log it or abort the whole run.
+#[derive(Debug)]
+pub struct Error {
+    input: Option<String>,
+    row: Option<u64>,
+    client: Option<ClientId>,
+    tx: Option<TransactionId>,
+    kind: ErrorKind,
+}
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    /// Reading the input failed.
+    Io(io::Error),
+    /// The row is not valid CSV or its fields have the wrong types.
+    Csv(csv::Error),
+    /// The line is not valid JSON or its fields have the wrong types.
+    Json(serde_json::Error),
+    /// The amount column could not be parsed.
+    Amount(ParseAmountError),
+    /// A deposit or withdrawal came without an amount.
+    MissingAmount,
+    /// Applying the transaction would overflow the client's balance.
+    Overflow,
+    /// A transfer came without the client receiving it.
+    MissingRecipient,
+    /// An unlock came without an audit reason.
+    MissingReason,
+    /// The currency column holds a value that is not a valid currency code.
+    InvalidCurrency(String),
+    /// The type column holds a value that is not a known transaction type.
+    UnknownType(String),
+    /// Inputs are merged by sequence, but the row has no sequence number.
+    MissingSequence,
+    /// Inputs are merged by sequence, but the row's sequence number is lower than the one
+    /// of the row before it in the same input.
+    OutOfSequence { seq: u64, previous: u64 },
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind) -> Self {
+        Self {
+            input: None,
+            row: None,
+            client: None,
+            tx: None,
+            kind,
+        }
+    }
+
+    /// Names the input the row comes from, when reading from several of them.
+    pub fn with_input(mut self, input: impl Into<String>) -> Self {
+        self.input = Some(input.into());
+        self
+    }
+
+    pub fn with_row(mut self, row: u64) -> Self {
+        self.row = Some(row);
+        self
+    }
+
+    pub fn with_transaction(mut self, client: ClientId, tx: TransactionId) -> Self {
+        self.client = Some(client);
+        self.tx = Some(tx);
+        self
+    }
+
+    pub fn input(&self) -> Option<&str> {
+        self.input.as_deref()
+    }
+
+    pub fn row(&self) -> Option<u64> {
+        self.row
+    }
+
+    pub fn client(&self) -> Option<ClientId> {
+        self.client
+    }
+
+    pub fn tx(&self) -> Option<TransactionId> {
+        self.tx
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self::new(kind)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::new(ErrorKind::Io(err))
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::new(ErrorKind::Json(err))
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(err: csv::Error) -> Self {
+        let row = err.position().map(|position| position.line());
+        Self {
+            row,
+            ..Self::new(ErrorKind::Csv(err))
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Io(err) => write!(f, "io error: {}", err),
+            ErrorKind::Csv(err) => write!(f, "malformed row: {}", err),
+            ErrorKind::Json(err) => write!(f, "malformed row: {}", err),
+            ErrorKind::Amount(err) => write!(f, "invalid amount: {}", err),
+            ErrorKind::MissingAmount => write!(f, "missing amount"),
+            ErrorKind::Overflow => write!(f, "balance overflow"),
+            ErrorKind::MissingRecipient => write!(f, "missing recipient"),
+            ErrorKind::MissingReason => write!(f, "missing reason"),
+            ErrorKind::InvalidCurrency(currency) => write!(f, "invalid currency `{}`", currency),
+            ErrorKind::UnknownType(r#type) => {
+                write!(f, "unknown transaction type `{}`", r#type)
+            }
+            ErrorKind::MissingSequence => write!(f, "missing sequence number"),
+            ErrorKind::OutOfSequence { seq, previous } => {
+                write!(f, "sequence number {} follows {}", seq, previous)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(input) = &self.input {
+            write!(f, "{}: ", input)?;
+        }
+        if let Some(row) = self.row {
+            write!(f, "row {}: ", row)?;
+        }
+        match (self.client, self.tx) {
+            (Some(client), Some(tx)) => write!(f, "client {}, tx {}: ", client, tx)?,
+            (Some(client), None) => write!(f, "client {}: ", client)?,
+            (None, Some(tx)) => write!(f, "tx {}: ", tx)?,
+            (None, None) => {}
+        }
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::Io(err) => Some(err),
+            ErrorKind::Csv(err) => Some(err),
+            ErrorKind::Json(err) => Some(err),
+            ErrorKind::Amount(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatting() {
+        let err = Error::new(ErrorKind::MissingAmount);
+        assert_eq!(format!("{}", err), "missing amount");
+
+        let err = err.with_transaction(1, 2);
+        assert_eq!(format!("{}", err), "client 1, tx 2: missing amount");
+
+        let err = Error::new(ErrorKind::UnknownType(String::from("refund")))
+            .with_transaction(3, 4)
+            .with_row(5);
+        assert_eq!(
+            format!("{}", err),
+            "row 5: client 3, tx 4: unknown transaction type `refund`"
+        );
+        assert_eq!(
+            format!("{}", err.with_input("b.csv")),
+            "b.csv: row 5: client 3, tx 4: unknown transaction type `refund`"
+        );
+
+        let err = Error::new(ErrorKind::Amount(ParseAmountError::TooManyDecimalPlaces(4)));
+        assert_eq!(
+            format!("{}", err),
+            "invalid amount: amount has more than 4 decimal places"
+        );
+    }
+}
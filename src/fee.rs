@@ -0,0 +1,144 @@
+use std::fmt;
+
+use crate::amount::RoundingMode;
+use crate::{Amount, ClientId, Currency, TransactionId};
+
+/// Fees the [`Database`](crate::Database) charges on top of transactions, selected through
+/// [`Config::fees`](crate::Config::fees). No fees are charged by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FeeSchedule {
+    /// Fee debited together with a withdrawal, which must be covered by the available funds.
+    /// It is refunded once the withdrawal is charged back in full.
+    pub withdrawal: Option<Fee>,
+    /// Fee debited when a deposit or withdrawal is charged back, even if that leaves the
+    /// available funds negative.
+    pub chargeback: Option<Fee>,
+}
+
+impl FeeSchedule {
+    pub fn fee(&self, kind: FeeKind) -> Option<&Fee> {
+        match kind {
+            FeeKind::Withdrawal => self.withdrawal.as_ref(),
+            FeeKind::Chargeback => self.chargeback.as_ref(),
+        }
+    }
+}
+
+/// Fee charged on a single type of transaction, a flat part plus a percentage of the
+/// transaction amount, kept within optional bounds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fee {
+    pub flat: Amount,
+    /// Percentage of the transaction amount, 1.5 stands for 1.5%.
+    pub percent: Amount,
+    pub min: Option<Amount>,
+    pub max: Option<Amount>,
+    /// How the fee is rounded to the scale of the transaction amount.
+    pub rounding: RoundingMode,
+}
+
+impl Fee {
+    /// Returns the fee charged on a transaction of `amount`, or `None` if it does not fit
+    /// into an [`Amount`].
+    pub fn charge(&self, amount: Amount) -> Option<Amount> {
+        let mut fee = self
+            .flat
+            .checked_add(amount.checked_percent(self.percent)?)?;
+        if let Some(min) = self.min {
+            fee = fee.max(min);
+        }
+        if let Some(max) = self.max {
+            fee = fee.min(max);
+        }
+        fee.rescale(amount.scale(), self.rounding)
+    }
+}
+
+impl Default for Fee {
+    fn default() -> Self {
+        Self {
+            flat: Amount::new(0),
+            percent: Amount::new(0),
+            min: None,
+            max: None,
+            rounding: RoundingMode::default(),
+        }
+    }
+}
+
+/// Type of transaction a fee is charged for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FeeKind {
+    Withdrawal,
+    Chargeback,
+}
+
+impl fmt::Display for FeeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            FeeKind::Withdrawal => "withdrawal",
+            FeeKind::Chargeback => "chargeback",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+/// Fee debited from a client, or refunded to it if the amount is negative.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeEntry {
+    pub client: ClientId,
+    /// Transaction the fee was charged for, the disputed one for chargeback fees.
+    pub tx: TransactionId,
+    pub kind: FeeKind,
+    pub amount: Amount,
+    pub currency: Currency,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn charging() {
+        let fee = Fee {
+            flat: Amount::new(5000),
+            percent: Amount::new(10000),
+            ..Fee::default()
+        };
+        assert_eq!(fee.charge(Amount::new(1000000)), Some(Amount::new(15000)));
+        assert_eq!(fee.charge(Amount::new(1000000)).unwrap().scale(), 4);
+
+        // 1% of 0.0150 is 0.00015, which rounds to the even 0.0002.
+        assert_eq!(fee.charge(Amount::new(150)), Some(Amount::new(5002)));
+        let down = Fee {
+            rounding: RoundingMode::TowardZero,
+            ..fee
+        };
+        assert_eq!(down.charge(Amount::new(150)), Some(Amount::new(5001)));
+
+        let bounded = Fee {
+            min: Some(Amount::new(10000)),
+            max: Some(Amount::new(20000)),
+            ..fee
+        };
+        assert_eq!(bounded.charge(Amount::new(10000)), Some(Amount::new(10000)));
+        assert_eq!(
+            bounded.charge(Amount::new(1000000)),
+            Some(Amount::new(15000))
+        );
+        assert_eq!(
+            bounded.charge(Amount::new(100000000)),
+            Some(Amount::new(20000))
+        );
+
+        // Fees on amounts of other scales take their scale.
+        let jpy = Fee {
+            percent: Amount::new(3333),
+            ..Fee::default()
+        };
+        assert_eq!(
+            jpy.charge(Amount::with_scale(1000, 0)),
+            Some(Amount::with_scale(3, 0))
+        );
+    }
+}
@@ -0,0 +1,813 @@
+mod jsonl;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use csv::{ByteRecord, ReaderBuilder, WriterBuilder};
+
+use crate::{Database, Error, ErrorKind, Outcome, Record, RejectReason};
+use jsonl::JsonlSource;
+
+/// What to do with an input row that cannot be turned into a valid transaction.
+#[derive(Clone, Debug, Default)]
+pub enum ErrorPolicy {
+    /// Stop processing and report the error.
+    #[default]
+    Abort,
+    /// Drop the row and keep going.
+    Skip,
+    /// Drop the row, copy it verbatim together with the error to a side file in the format
+    /// of the input and keep going.
+    Quarantine(PathBuf),
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            None if s == "abort" => Ok(ErrorPolicy::Abort),
+            None if s == "skip" => Ok(ErrorPolicy::Skip),
+            Some(("quarantine", path)) if !path.is_empty() => {
+                Ok(ErrorPolicy::Quarantine(PathBuf::from(path)))
+            }
+            _ => Err(format!(
+                "invalid error policy `{}`, expected `abort`, `skip` or `quarantine=<path>`",
+                s
+            )),
+        }
+    }
+}
+
+/// Counts of rows seen while reading the input.
+#[derive(Debug, Default)]
+pub struct ReadSummary {
+    /// Number of data rows read, not counting the header.
+    pub rows: u64,
+    /// Number of invalid rows that were skipped or quarantined.
+    pub invalid: u64,
+    /// Number of valid transactions, by type, whether they took effect or not.
+    pub types: BTreeMap<&'static str, u64>,
+    /// Number of valid transactions that did not take effect, by reason.
+    pub rejected: BTreeMap<RejectReason, u64>,
+    /// Number of transactions dropped as repeats of earlier ones.
+    pub duplicates: u64,
+    /// Number of disputes that could hold only part of the amount because of the overdraft
+    /// limit.
+    pub partial_holds: u64,
+}
+
+impl ReadSummary {
+    /// Total number of valid transactions that did not take effect.
+    pub fn rejected_total(&self) -> u64 {
+        self.rejected.values().sum()
+    }
+}
+
+/// Input to read transactions from, along with the name errors in it are reported with.
+pub struct Input<R> {
+    pub name: String,
+    pub reader: R,
+}
+
+impl<R: Read> Input<R> {
+    pub fn new(name: impl Into<String>, reader: R) -> Self {
+        Self {
+            name: name.into(),
+            reader,
+        }
+    }
+}
+
+/// How the rows of several inputs are combined.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Merge {
+    /// One input after the other, in the order they are given.
+    #[default]
+    Concatenate,
+    /// In the order of the `seq` column, which each input must already be sorted by. Rows
+    /// with the same sequence number are taken in the order of the inputs.
+    Sequence,
+}
+
+impl FromStr for Merge {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "concatenate" => Ok(Merge::Concatenate),
+            "sequence" => Ok(Merge::Sequence),
+            _ => Err(format!(
+                "invalid merge mode `{}`, expected `concatenate` or `sequence`",
+                s
+            )),
+        }
+    }
+}
+
+/// Format of the input rows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputFormat {
+    /// CSV with a header row naming the columns.
+    #[default]
+    Csv,
+    /// JSON Lines, one object per line with the columns as fields. Amounts may be strings
+    /// or numbers, which are parsed from the text they are written as, so they are as exact
+    /// as in CSV. Blank lines are skipped.
+    Jsonl,
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(InputFormat::Csv),
+            "jsonl" => Ok(InputFormat::Jsonl),
+            _ => Err(format!(
+                "invalid input format `{}`, expected `csv` or `jsonl`",
+                s
+            )),
+        }
+    }
+}
+
+/// How inputs are read and combined.
+#[derive(Clone, Debug)]
+pub struct ReadOptions {
+    pub format: InputFormat,
+    pub policy: ErrorPolicy,
+    pub merge: Merge,
+    /// Field delimiter of CSV input, also used for quarantined rows. A comma by default.
+    pub delimiter: u8,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            format: InputFormat::default(),
+            policy: ErrorPolicy::default(),
+            merge: Merge::default(),
+            delimiter: b',',
+        }
+    }
+}
+
+/// Reads CSV transactions from `reader` and applies them to `database` in order.
+///
+/// Currencies and amounts are parsed as configured for the database. Rows that cannot be
+/// parsed or applied are handled according to `policy`. Errors that prevent reading the
+/// rest of the input, such as IO errors, are always returned.
+pub fn read_csv<R: Read>(
+    reader: R,
+    database: &mut Database,
+    policy: &ErrorPolicy,
+) -> Result<ReadSummary, Error> {
+    let options = ReadOptions {
+        policy: policy.clone(),
+        ..ReadOptions::default()
+    };
+    read_inputs(vec![Input::new("", reader)], database, &options)
+}
+
+/// Reads JSON Lines transactions from `reader` and applies them to `database` like
+/// [`read_csv`] does.
+pub fn read_jsonl<R: Read>(
+    reader: R,
+    database: &mut Database,
+    policy: &ErrorPolicy,
+) -> Result<ReadSummary, Error> {
+    let options = ReadOptions {
+        policy: policy.clone(),
+        format: InputFormat::Jsonl,
+        ..ReadOptions::default()
+    };
+    read_inputs(vec![Input::new("", reader)], database, &options)
+}
+
+/// Reads transactions from several inputs in [`ReadOptions::format`], combined as
+/// [`ReadOptions::merge`] says, and applies them to `database` like [`read_csv`] does.
+///
+/// Inputs are read as a stream, only a single row of each is kept in memory. Errors name
+/// the input they come from when there is more than one, and quarantined rows of all
+/// inputs go to the same file, under the header of the first input for CSV. When merging
+/// by sequence, rows without a sequence number or with one lower than the row before them
+/// in the same input are invalid.
+pub fn read_inputs<'r, R: Read + 'r>(
+    inputs: Vec<Input<R>>,
+    database: &mut Database,
+    options: &ReadOptions,
+) -> Result<ReadSummary, Error> {
+    let named = inputs.len() > 1;
+    let mut header = None;
+    let mut sources = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let name = named.then_some(input.name);
+        let rows: Box<dyn RecordSource + 'r> = match options.format {
+            InputFormat::Csv => {
+                let source = CsvSource::new(input.reader, options.delimiter)
+                    .map_err(|err| with_input(err, &name))?;
+                header.get_or_insert_with(|| source.headers.clone());
+                Box::new(source)
+            }
+            InputFormat::Jsonl => Box::new(JsonlSource::new(input.reader)),
+        };
+        sources.push(Source {
+            name,
+            rows,
+            seq: None,
+        });
+    }
+
+    let quarantine = match &options.policy {
+        ErrorPolicy::Quarantine(path) if !sources.is_empty() => {
+            Some(Quarantine::create(path, options, header.as_ref())?)
+        }
+        _ => None,
+    };
+    let mut reader = Reader {
+        database,
+        policy: &options.policy,
+        quarantine,
+        summary: ReadSummary::default(),
+    };
+
+    match options.merge {
+        Merge::Concatenate => {
+            for source in &mut sources {
+                while let Some(row) = source.next_row()? {
+                    reader.apply(source, row)?;
+                }
+            }
+        }
+        Merge::Sequence => {
+            let mut heads = Vec::with_capacity(sources.len());
+            for source in &mut sources {
+                heads.push(reader.next_in_sequence(source)?);
+            }
+            // Picks the input whose next row comes first, the earliest input among equals.
+            while let Some(next) = heads
+                .iter()
+                .enumerate()
+                .filter_map(|(i, head)| head.as_ref().map(|(seq, _)| (*seq, i)))
+                .min()
+                .map(|(_, i)| i)
+            {
+                let (_, row) = heads[next].take().expect("picked above");
+                reader.apply(&sources[next], row)?;
+                heads[next] = reader.next_in_sequence(&mut sources[next])?;
+            }
+        }
+    }
+
+    if let Some(quarantine) = reader.quarantine {
+        quarantine.flush()?;
+    }
+    Ok(reader.summary)
+}
+
+/// An input format [`Record`]s can come from, read one row at a time.
+trait RecordSource {
+    /// Returns the next row, or `None` at the end of the input.
+    fn next_row(&mut self) -> Result<Option<Row>, Error>;
+}
+
+/// A row as read from its input, along with the record parsed from it.
+struct Row {
+    raw: RawRow,
+    line: u64,
+    record: Result<Record, Error>,
+}
+
+/// A row as it appeared in the input, for quarantining it.
+enum RawRow {
+    /// Fields of a CSV row, along with the number of columns of the input.
+    Csv { fields: ByteRecord, columns: usize },
+    /// A line of JSON, without the line break.
+    Json(Vec<u8>),
+}
+
+/// A single input, along with what is known about it while reading.
+struct Source<'r> {
+    /// Name errors are reported with, if there are several inputs.
+    name: Option<String>,
+    rows: Box<dyn RecordSource + 'r>,
+    /// Sequence number of the last row, when merging by sequence.
+    seq: Option<u64>,
+}
+
+impl Source<'_> {
+    fn next_row(&mut self) -> Result<Option<Row>, Error> {
+        self.rows
+            .next_row()
+            .map_err(|err| with_input(err, &self.name))
+    }
+}
+
+/// A CSV input, whose columns are named by its header.
+struct CsvSource<R> {
+    rdr: csv::Reader<R>,
+    headers: ByteRecord,
+}
+
+impl<R: Read> CsvSource<R> {
+    fn new(reader: R, delimiter: u8) -> Result<Self, Error> {
+        let mut rdr = ReaderBuilder::new()
+            .flexible(true)
+            .delimiter(delimiter)
+            .from_reader(reader);
+        let mut headers = rdr.byte_headers()?.clone();
+        headers.trim();
+        Ok(Self { rdr, headers })
+    }
+}
+
+impl<R: Read> RecordSource for CsvSource<R> {
+    fn next_row(&mut self) -> Result<Option<Row>, Error> {
+        let mut fields = ByteRecord::new();
+        if !self.rdr.read_byte_record(&mut fields)? {
+            return Ok(None);
+        }
+        let line = fields.position().map_or(0, |position| position.line());
+        let mut trimmed = fields.clone();
+        trimmed.trim();
+        let record = trimmed
+            .deserialize::<Record>(Some(&self.headers))
+            .map_err(Error::from);
+        let columns = self.headers.len();
+        Ok(Some(Row {
+            raw: RawRow::Csv { fields, columns },
+            line,
+            record,
+        }))
+    }
+}
+
+/// Side file invalid rows are copied to, in the format of the input.
+enum Quarantine {
+    /// The fields of the row followed by an `error` column.
+    Csv(Box<csv::Writer<File>>),
+    /// An object per row, with the line as it was read in `input` and the error in
+    /// `error`.
+    Jsonl(BufWriter<File>),
+}
+
+impl Quarantine {
+    fn create(
+        path: &Path,
+        options: &ReadOptions,
+        header: Option<&ByteRecord>,
+    ) -> Result<Self, Error> {
+        match options.format {
+            InputFormat::Csv => {
+                let mut writer = WriterBuilder::new()
+                    .flexible(true)
+                    .delimiter(options.delimiter)
+                    .from_path(path)?;
+                writer.write_record(header.into_iter().flatten().chain([&b"error"[..]]))?;
+                Ok(Quarantine::Csv(Box::new(writer)))
+            }
+            InputFormat::Jsonl => Ok(Quarantine::Jsonl(BufWriter::new(File::create(path)?))),
+        }
+    }
+
+    fn write(&mut self, raw: &RawRow, error: &Error) -> Result<(), Error> {
+        let error = error.to_string();
+        match (self, raw) {
+            (Quarantine::Csv(writer), RawRow::Csv { fields, columns }) => {
+                // Pad short rows so that the error always lands in its own column.
+                let padding = columns.saturating_sub(fields.len());
+                writer.write_record(
+                    fields
+                        .iter()
+                        .chain(std::iter::repeat_n(&b""[..], padding))
+                        .chain([error.as_bytes()]),
+                )?;
+            }
+            (Quarantine::Jsonl(writer), RawRow::Json(line)) => {
+                let line = String::from_utf8_lossy(line);
+                writeln!(
+                    writer,
+                    r#"{{"input":{},"error":{}}}"#,
+                    serde_json::Value::from(line),
+                    serde_json::Value::from(error)
+                )?;
+            }
+            _ => unreachable!("quarantine and input have the same format"),
+        }
+        Ok(())
+    }
+
+    fn flush(self) -> Result<(), Error> {
+        match self {
+            Quarantine::Csv(mut writer) => writer.flush()?,
+            Quarantine::Jsonl(mut writer) => writer.flush()?,
+        }
+        Ok(())
+    }
+}
+
+/// Applies rows to the database and keeps track of what happened to them.
+struct Reader<'a> {
+    database: &'a mut Database,
+    policy: &'a ErrorPolicy,
+    quarantine: Option<Quarantine>,
+    summary: ReadSummary,
+}
+
+impl Reader<'_> {
+    fn apply(&mut self, source: &Source, row: Row) -> Result<(), Error> {
+        let database = &mut *self.database;
+        let summary = &mut self.summary;
+        summary.rows += 1;
+        let result = row
+            .record
+            .and_then(|record| database.transaction_from_record(record))
+            .and_then(|tx| {
+                *summary.types.entry(tx.type_name()).or_insert(0) += 1;
+                database.add_transaction(tx)
+            })
+            .map_err(|err| with_input(err.with_row(row.line), &source.name));
+        match result {
+            Ok(Outcome::Applied) => {}
+            Ok(Outcome::Rejected(reason)) => *summary.rejected.entry(reason).or_insert(0) += 1,
+            Ok(Outcome::Duplicate) => summary.duplicates += 1,
+            Ok(Outcome::PartiallyHeld(_)) => summary.partial_holds += 1,
+            Err(err) => {
+                if let Some(quarantine) = self.quarantine.as_mut() {
+                    quarantine.write(&row.raw, &err)?;
+                } else if let ErrorPolicy::Abort = self.policy {
+                    return Err(err);
+                }
+                summary.invalid += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the next row of `source` that can be merged by sequence, along with its
+    /// sequence number. Rows that cannot be placed in the sequence are applied, and so
+    /// reported as invalid, right away.
+    fn next_in_sequence(&mut self, source: &mut Source) -> Result<Option<(u64, Row)>, Error> {
+        while let Some(mut row) = source.next_row()? {
+            let seq = match &row.record {
+                Ok(record) => record.seq,
+                Err(_) => {
+                    self.apply(source, row)?;
+                    continue;
+                }
+            };
+            let context = |kind| {
+                let err = Error::new(kind);
+                match &row.record {
+                    Ok(record) => err.with_transaction(record.client, record.tx),
+                    Err(_) => err,
+                }
+            };
+            let err = match (seq, source.seq) {
+                (Some(seq), Some(previous)) if seq < previous => {
+                    context(ErrorKind::OutOfSequence { seq, previous })
+                }
+                (Some(seq), _) => {
+                    source.seq = Some(seq);
+                    return Ok(Some((seq, row)));
+                }
+                (None, _) => context(ErrorKind::MissingSequence),
+            };
+            row.record = Err(err);
+            self.apply(source, row)?;
+        }
+        Ok(None)
+    }
+}
+
+fn with_input(err: Error, name: &Option<String>) -> Error {
+    match name {
+        Some(name) => err.with_input(name.as_str()),
+        None => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use std::path::Path;
+
+    const INPUT: &str = "type, client, tx, amount
+deposit, 1, 1, 1.0
+refund, 1, 2, 1.0
+deposit, 1, 3
+withdrawal, 1, 4, 0.25
+";
+
+    #[test]
+    fn parsing_policy() {
+        assert!(matches!("abort".parse(), Ok(ErrorPolicy::Abort)));
+        assert!(matches!("skip".parse(), Ok(ErrorPolicy::Skip)));
+        assert!(
+            matches!("quarantine=bad.csv".parse(), Ok(ErrorPolicy::Quarantine(p)) if p == Path::new("bad.csv"))
+        );
+        assert!("quarantine".parse::<ErrorPolicy>().is_err());
+        assert!("quarantine=".parse::<ErrorPolicy>().is_err());
+        assert!("ignore".parse::<ErrorPolicy>().is_err());
+    }
+
+    #[test]
+    fn abort_on_bad_row() {
+        let mut database = Database::new();
+        let err = read_csv(INPUT.as_bytes(), &mut database, &ErrorPolicy::Abort).unwrap_err();
+        assert_eq!(err.row(), Some(3));
+        assert_eq!(database.get_entry(1).unwrap().to_string(), "1,1,0,1,false");
+    }
+
+    #[test]
+    fn skip_bad_rows() {
+        let mut database = Database::new();
+        let summary = read_csv(INPUT.as_bytes(), &mut database, &ErrorPolicy::Skip).unwrap();
+        assert_eq!((summary.rows, summary.invalid), (4, 2));
+        assert_eq!(summary.rejected_total(), 0);
+        assert_eq!(
+            database.get_entry(1).unwrap().to_string(),
+            "1,0.75,0,0.75,false"
+        );
+    }
+
+    #[test]
+    fn quarantine_bad_rows() {
+        let path = std::env::temp_dir().join(format!("quarantine-{}.csv", std::process::id()));
+        let mut database = Database::new();
+        let policy = ErrorPolicy::Quarantine(path.clone());
+        let summary = read_csv(INPUT.as_bytes(), &mut database, &policy).unwrap();
+        assert_eq!((summary.rows, summary.invalid), (4, 2));
+
+        let quarantined = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            quarantined,
+            "type,client,tx,amount,error
+refund, 1, 2, 1.0,\"row 3: client 1, tx 2: unknown transaction type `refund`\"
+deposit, 1, 3,,\"row 4: client 1, tx 3: missing amount\"
+"
+        );
+    }
+
+    #[test]
+    fn policies_keep_io_errors() {
+        /// Reader failing after the header and first row.
+        struct Failing<'a>(&'a [u8]);
+
+        impl Read for Failing<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if self.0.is_empty() {
+                    return Err(std::io::Error::other("disk on fire"));
+                }
+                self.0.read(buf)
+            }
+        }
+
+        let input = b"type,client,tx,amount\ndeposit,1,1,1\n";
+        for policy in [ErrorPolicy::Abort, ErrorPolicy::Skip] {
+            let mut database = Database::new();
+            let err = read_csv(Failing(input), &mut database, &policy).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::Csv(err) if err.is_io_error()));
+            assert_eq!(database.get_entry(1).unwrap().to_string(), "1,1,0,1,false");
+        }
+
+        // The quarantine file cannot be created.
+        let policy = ErrorPolicy::Quarantine(PathBuf::from("/nonexistent/quarantine.csv"));
+        let err = read_csv(INPUT.as_bytes(), &mut Database::new(), &policy).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Csv(_) | ErrorKind::Io(_)));
+    }
+
+    #[test]
+    fn count_rejected_transactions() {
+        let input = "type,client,tx,amount
+deposit,1,1,1
+withdrawal,1,2,5
+withdrawal,1,3,5
+dispute,1,9,
+resolve,1,1,
+";
+        let mut database = Database::new();
+        let summary = read_csv(input.as_bytes(), &mut database, &ErrorPolicy::Abort).unwrap();
+        assert_eq!(summary.rows, 5);
+        assert_eq!(
+            summary.types,
+            BTreeMap::from([
+                ("deposit", 1),
+                ("dispute", 1),
+                ("resolve", 1),
+                ("withdrawal", 2)
+            ])
+        );
+        assert_eq!(summary.rejected_total(), 4);
+        assert_eq!(
+            summary.rejected.into_iter().collect::<Vec<_>>(),
+            vec![
+                (RejectReason::InsufficientFunds, 2),
+                (RejectReason::UnknownTx, 1),
+                (RejectReason::NotDisputed, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn other_delimiter() {
+        let input = "type\tclient\ttx\tamount\ndeposit\t1\t1\t1.5\n";
+        let options = ReadOptions {
+            delimiter: b'\t',
+            ..ReadOptions::default()
+        };
+        let mut database = Database::new();
+        read_inputs(
+            vec![Input::new("", input.as_bytes())],
+            &mut database,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            database.get_entry(1).unwrap().to_string(),
+            "1,1.5,0,1.5,false"
+        );
+    }
+
+    #[test]
+    fn parsing_merge() {
+        assert_eq!("concatenate".parse(), Ok(Merge::Concatenate));
+        assert_eq!("sequence".parse(), Ok(Merge::Sequence));
+        assert!("seq".parse::<Merge>().is_err());
+    }
+
+    #[test]
+    fn concatenate_inputs() {
+        let inputs = vec![
+            Input::new("a.csv", "type,client,tx,amount\ndeposit,1,1,2\n".as_bytes()),
+            Input::new(
+                "b.csv",
+                "type,client,tx,amount\nwithdrawal,1,2,0.5\n".as_bytes(),
+            ),
+        ];
+        let mut database = Database::new();
+        let summary = read_inputs(inputs, &mut database, &ReadOptions::default()).unwrap();
+        assert_eq!(summary.rows, 2);
+        assert_eq!(
+            database.get_entry(1).unwrap().to_string(),
+            "1,1.5,0,1.5,false"
+        );
+
+        // Errors name the input they come from.
+        let inputs = vec![
+            Input::new("a.csv", "type,client,tx,amount\ndeposit,1,3,2\n".as_bytes()),
+            Input::new("b.csv", "type,client,tx,amount\ndeposit,1,4\n".as_bytes()),
+        ];
+        let err = read_inputs(inputs, &mut database, &ReadOptions::default()).unwrap_err();
+        assert_eq!(err.input(), Some("b.csv"));
+        assert_eq!(
+            err.to_string(),
+            "b.csv: row 2: client 1, tx 4: missing amount"
+        );
+    }
+
+    #[test]
+    fn merge_inputs_by_sequence() {
+        let deposits = "type,client,tx,amount,seq
+deposit,1,1,1,1
+deposit,1,3,1,4
+deposit,1,5,1,4
+deposit,1,6,1,3
+deposit,1,7,1,
+";
+        let withdrawals = "type,client,tx,amount,seq
+withdrawal,1,2,1,2
+withdrawal,1,4,2,4
+";
+        let inputs = vec![
+            Input::new("deposits.csv", deposits.as_bytes()),
+            Input::new("withdrawals.csv", withdrawals.as_bytes()),
+        ];
+        let mut database = Database::new();
+        let options = ReadOptions {
+            policy: ErrorPolicy::Skip,
+            merge: Merge::Sequence,
+            ..ReadOptions::default()
+        };
+        let summary = read_inputs(inputs, &mut database, &options).unwrap();
+        // The withdrawal of 2 comes after both deposits with the same sequence number, and
+        // the deposit without one or out of sequence is skipped.
+        assert_eq!((summary.rows, summary.invalid), (7, 2));
+        assert_eq!(summary.rejected_total(), 0);
+        assert_eq!(database.get_entry(1).unwrap().to_string(), "1,0,0,0,false");
+
+        let inputs = vec![Input::new("deposits.csv", deposits.as_bytes())];
+        let options = ReadOptions {
+            merge: Merge::Sequence,
+            ..ReadOptions::default()
+        };
+        let err = read_inputs(inputs, &mut database, &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "row 5: client 1, tx 6: sequence number 3 follows 4"
+        );
+    }
+
+    #[test]
+    fn parsing_input_format() {
+        assert_eq!("csv".parse(), Ok(InputFormat::Csv));
+        assert_eq!("jsonl".parse(), Ok(InputFormat::Jsonl));
+        assert!("json".parse::<InputFormat>().is_err());
+    }
+
+    #[test]
+    fn jsonl_amounts() {
+        let input = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"}
+
+{"type": "deposit", "client": 1, "tx": 2, "amount": 2.0001, "currency": "USD"}
+{"type": "withdrawal", "client": 1, "tx": 3, "amount": 0.1}
+{"type": "dispute", "client": 1, "tx": 1, "amount": null}
+"#;
+        let mut database = Database::new();
+        let summary = read_jsonl(input.as_bytes(), &mut database, &ErrorPolicy::Abort).unwrap();
+        assert_eq!((summary.rows, summary.invalid), (4, 0));
+        assert_eq!(
+            database.get_entry(1).unwrap().to_string(),
+            "1,1.9001,1.5,3.4001,false"
+        );
+
+        // Numbers are parsed from their text, so excess precision is caught as in CSV.
+        let input = r#"{"type": "deposit", "client": 1, "tx": 4, "amount": 0.00001}"#;
+        let err = read_jsonl(input.as_bytes(), &mut database, &ErrorPolicy::Abort).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "row 1: client 1, tx 4: invalid amount: amount has more than 4 decimal places"
+        );
+    }
+
+    #[test]
+    fn quarantine_bad_jsonl_lines() {
+        let input = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": 1}
+{"type": "deposit", "client": 1, "tx": 2, "amount": true}
+not json
+{"type": "withdrawal", "client": 1, "tx": 3, "amount": "0.25"}
+"#;
+        let path = std::env::temp_dir().join(format!("quarantine-{}.jsonl", std::process::id()));
+        let mut database = Database::new();
+        let policy = ErrorPolicy::Quarantine(path.clone());
+        let summary = read_jsonl(input.as_bytes(), &mut database, &policy).unwrap();
+        assert_eq!((summary.rows, summary.invalid), (4, 2));
+        assert_eq!(
+            database.get_entry(1).unwrap().to_string(),
+            "1,0.75,0,0.75,false"
+        );
+
+        let quarantined = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<serde_json::Value> = quarantined
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0]["input"],
+            r#"{"type": "deposit", "client": 1, "tx": 2, "amount": true}"#
+        );
+        assert!(lines[0]["error"].as_str().unwrap().starts_with(
+            "row 2: malformed row: invalid type: true, expected a string or a number"
+        ));
+        assert_eq!(lines[1]["input"], "not json");
+        assert!(lines[1]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("row 3: malformed row:"));
+    }
+
+    #[test]
+    fn optional_currency_column() {
+        let input = "type,client,tx,amount,currency
+deposit,1,1,1.5,
+deposit,1,2,100,jpy
+withdrawal,1,3,30,JPY
+dispute,1,2,,
+";
+        let mut config = Config::default();
+        config.precision.scales.insert("JPY".parse().unwrap(), 0);
+        let mut database = Database::with_config(config);
+        read_csv(input.as_bytes(), &mut database, &ErrorPolicy::Abort).unwrap();
+        let mut entries: Vec<_> = database
+            .entries()
+            .map(|entry| format!("{} {}", entry.currency(), entry))
+            .collect();
+        entries.sort();
+        assert_eq!(entries, ["JPY 1,-30,100,70,false", "USD 1,1.5,0,1.5,false"]);
+
+        let input = "type,client,tx,amount,currency\ndeposit,1,4,0.5,JPY\n";
+        let err = read_csv(input.as_bytes(), &mut database, &ErrorPolicy::Abort).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "row 2: client 1, tx 4: invalid amount: amount has more than 0 decimal places"
+        );
+    }
+}
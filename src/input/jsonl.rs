@@ -0,0 +1,102 @@
+use std::io::{BufRead, BufReader, Read};
+
+use serde::{de, Deserialize, Deserializer};
+use serde_json::value::RawValue;
+
+use super::{RawRow, RecordSource, Row};
+use crate::{ClientId, Error, Record, TransactionId};
+
+/// A JSON Lines input, an object per line.
+pub(super) struct JsonlSource<R> {
+    reader: BufReader<R>,
+    line: u64,
+}
+
+impl<R: Read> JsonlSource<R> {
+    pub(super) fn new(reader: R) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            line: 0,
+        }
+    }
+}
+
+impl<R: Read> RecordSource for JsonlSource<R> {
+    fn next_row(&mut self) -> Result<Option<Row>, Error> {
+        loop {
+            let mut line = Vec::new();
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+            if line.ends_with(b"\n") {
+                line.pop();
+                if line.ends_with(b"\r") {
+                    line.pop();
+                }
+            }
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            let record = serde_json::from_slice::<JsonRecord>(&line)
+                .map(Record::from)
+                .map_err(Error::from);
+            return Ok(Some(Row {
+                raw: RawRow::Json(line),
+                line: self.line,
+                record,
+            }));
+        }
+    }
+}
+
+/// A [`Record`] as written in JSON.
+#[derive(Deserialize)]
+struct JsonRecord {
+    r#type: String,
+    client: ClientId,
+    tx: TransactionId,
+    amount: Option<JsonAmount>,
+    currency: Option<String>,
+    to_client: Option<ClientId>,
+    reason: Option<String>,
+    seq: Option<u64>,
+}
+
+impl From<JsonRecord> for Record {
+    fn from(record: JsonRecord) -> Self {
+        Record {
+            r#type: record.r#type,
+            client: record.client,
+            tx: record.tx,
+            amount: record.amount.map(|amount| amount.0),
+            currency: record.currency,
+            to_client: record.to_client,
+            reason: record.reason,
+            seq: record.seq,
+        }
+    }
+}
+
+/// Amount given as a JSON string or number, kept as the text it is written as so that it
+/// is parsed exactly like in CSV rather than going through a float.
+struct JsonAmount(String);
+
+impl<'de> Deserialize<'de> for JsonAmount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = Box::<RawValue>::deserialize(deserializer)?;
+        let text = raw.get();
+        if text.starts_with('"') {
+            serde_json::from_str(text)
+                .map(JsonAmount)
+                .map_err(de::Error::custom)
+        } else if text.starts_with(|c: char| c == '-' || c.is_ascii_digit()) {
+            Ok(JsonAmount(text.to_owned()))
+        } else {
+            Err(de::Error::invalid_type(
+                de::Unexpected::Other(text),
+                &"a string or a number",
+            ))
+        }
+    }
+}
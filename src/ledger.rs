@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{error::ErrorKind, Amount, ClientId, Currency, TransactionId};
+
+/// Account of the [`Ledger`].
+///
+/// Postings to the accounts of a client add to the client's funds. Every journal entry sums
+/// to zero, so the system accounts carry the opposite of what clients gained: after a
+/// deposit of 10, `client:1:available` is at 10 and `system:cash` at -10.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Account {
+    /// Funds of a client that can be withdrawn.
+    Available(ClientId),
+    /// Funds of a client held by open disputes.
+    Held(ClientId),
+    /// Funds entering and leaving the system through deposits, withdrawals and chargebacks
+    /// of deposits.
+    Cash,
+    /// Funds credited back to clients by disputes of withdrawals.
+    ChargebackLoss,
+    /// Fees charged to clients.
+    Fees,
+}
+
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Account::Available(client) => write!(f, "client:{}:available", client),
+            Account::Held(client) => write!(f, "client:{}:held", client),
+            Account::Cash => write!(f, "system:cash"),
+            Account::ChargebackLoss => write!(f, "system:chargeback_loss"),
+            Account::Fees => write!(f, "system:fees"),
+        }
+    }
+}
+
+/// Change to the balance of an account in a single currency.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Posting {
+    pub account: Account,
+    pub currency: Currency,
+    pub amount: Amount,
+}
+
+/// Postings made for a transaction, which sum to zero in each currency.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub tx: TransactionId,
+    pub postings: Vec<Posting>,
+}
+
+/// Double-entry ledger every balance change of the [`Database`](crate::Database) is posted
+/// to. Client balances are projections of its accounts.
+#[derive(Debug, Default)]
+pub struct Ledger {
+    journal: Vec<JournalEntry>,
+    balances: HashMap<(Account, Currency), Amount>,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the balance of an account in `currency`, which is zero if nothing was posted
+    /// to it.
+    pub fn balance(&self, account: Account, currency: Currency) -> Amount {
+        self.balances
+            .get(&(account, currency))
+            .copied()
+            .unwrap_or(Amount::new(0))
+    }
+
+    /// Returns the entries posted so far, in the order they were posted.
+    pub fn journal(&self) -> &[JournalEntry] {
+        &self.journal
+    }
+
+    /// Posts an entry, leaving the ledger unchanged if the balance of an account, or the
+    /// sum of the available and held funds of a client, would overflow.
+    ///
+    /// # Panics
+    ///
+    /// If the entry does not sum to zero in each currency.
+    pub fn post(&mut self, entry: JournalEntry) -> Result<(), ErrorKind> {
+        self.post_all(vec![entry])
+    }
+
+    /// Posts several entries in order as a whole, like [`post`](Ledger::post) does with a
+    /// single one. If any of them cannot be posted, none is.
+    ///
+    /// # Panics
+    ///
+    /// If an entry does not sum to zero in each currency.
+    pub fn post_all(&mut self, entries: Vec<JournalEntry>) -> Result<(), ErrorKind> {
+        for entry in &entries {
+            assert!(
+                sums_to_zero(&entry.postings),
+                "unbalanced journal entry for tx {}",
+                entry.tx
+            );
+        }
+        let mut updated: HashMap<(Account, Currency), Amount> = HashMap::new();
+        for posting in entries.iter().flat_map(|entry| &entry.postings) {
+            let key = (posting.account, posting.currency);
+            let balance = updated
+                .get(&key)
+                .copied()
+                .unwrap_or_else(|| self.balance(posting.account, posting.currency));
+            let balance = balance
+                .checked_add(posting.amount)
+                .ok_or(ErrorKind::Overflow)?;
+            updated.insert(key, balance);
+        }
+        let balance = |account, currency| {
+            updated
+                .get(&(account, currency))
+                .copied()
+                .unwrap_or_else(|| self.balance(account, currency))
+        };
+        for &(account, currency) in updated.keys() {
+            if let Account::Available(client) | Account::Held(client) = account {
+                balance(Account::Available(client), currency)
+                    .checked_add(balance(Account::Held(client), currency))
+                    .ok_or(ErrorKind::Overflow)?;
+            }
+        }
+        self.balances.extend(updated);
+        self.journal.extend(entries);
+        Ok(())
+    }
+
+    /// Replays the journal to prove that every entry, and so all postings together, sum to
+    /// zero in each currency, and that the balances of all accounts follow from it.
+    pub fn verify(&self) -> bool {
+        let mut balances: HashMap<(Account, Currency), Amount> = HashMap::new();
+        for entry in &self.journal {
+            if !sums_to_zero(&entry.postings) {
+                return false;
+            }
+            for posting in &entry.postings {
+                let balance = balances
+                    .entry((posting.account, posting.currency))
+                    .or_insert(Amount::new(0));
+                match balance.checked_add(posting.amount) {
+                    Some(sum) => *balance = sum,
+                    None => return false,
+                }
+            }
+        }
+        balances == self.balances
+    }
+}
+
+fn sums_to_zero(postings: &[Posting]) -> bool {
+    let mut sums: HashMap<Currency, Amount> = HashMap::new();
+    for posting in postings {
+        let sum = sums.entry(posting.currency).or_insert(Amount::new(0));
+        match sum.checked_add(posting.amount) {
+            Some(total) => *sum = total,
+            None => return false,
+        }
+    }
+    sums.values().all(|&sum| sum == Amount::new(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::UnderlyingAmountType;
+
+    fn posting(account: Account, amount: UnderlyingAmountType) -> Posting {
+        Posting {
+            account,
+            currency: Currency::default(),
+            amount: Amount::new(amount),
+        }
+    }
+
+    #[test]
+    fn posting_entries() {
+        let usd = Currency::default();
+        let mut ledger = Ledger::new();
+        ledger
+            .post(JournalEntry {
+                tx: 1,
+                postings: vec![
+                    posting(Account::Available(1), 10000),
+                    posting(Account::Cash, -10000),
+                ],
+            })
+            .unwrap();
+        ledger
+            .post(JournalEntry {
+                tx: 2,
+                postings: vec![
+                    posting(Account::Available(1), -6000),
+                    posting(Account::Cash, 5000),
+                    posting(Account::Fees, 1000),
+                ],
+            })
+            .unwrap();
+        assert_eq!(
+            ledger.balance(Account::Available(1), usd),
+            Amount::new(4000)
+        );
+        assert_eq!(ledger.balance(Account::Cash, usd), Amount::new(-5000));
+        assert_eq!(ledger.balance(Account::Held(1), usd), Amount::new(0));
+        assert_eq!(ledger.journal().len(), 2);
+        assert!(ledger.verify());
+
+        // The sum of the client's funds would overflow, nothing is posted.
+        let max = UnderlyingAmountType::MAX;
+        let err = ledger
+            .post(JournalEntry {
+                tx: 3,
+                postings: vec![posting(Account::Held(1), max), posting(Account::Fees, -max)],
+            })
+            .unwrap_err();
+        assert!(matches!(err, ErrorKind::Overflow));
+        assert_eq!(ledger.balance(Account::Fees, usd), Amount::new(1000));
+        assert_eq!(ledger.journal().len(), 2);
+        assert!(ledger.verify());
+    }
+
+    #[test]
+    fn posting_entries_as_a_whole() {
+        let usd = Currency::default();
+        let max = UnderlyingAmountType::MAX;
+        let mut ledger = Ledger::new();
+        let entry = |tx, postings| JournalEntry { tx, postings };
+        ledger
+            .post_all(vec![
+                entry(
+                    1,
+                    vec![
+                        posting(Account::Available(1), 10000),
+                        posting(Account::Cash, -10000),
+                    ],
+                ),
+                entry(
+                    1,
+                    vec![
+                        posting(Account::Available(1), -1000),
+                        posting(Account::Fees, 1000),
+                    ],
+                ),
+            ])
+            .unwrap();
+        assert_eq!(ledger.journal().len(), 2);
+        assert_eq!(
+            ledger.balance(Account::Available(1), usd),
+            Amount::new(9000)
+        );
+
+        // The second entry overflows, so the first one is not posted either.
+        let err = ledger
+            .post_all(vec![
+                entry(
+                    2,
+                    vec![
+                        posting(Account::Available(2), 10000),
+                        posting(Account::Cash, -10000),
+                    ],
+                ),
+                entry(
+                    2,
+                    vec![posting(Account::Fees, max), posting(Account::Cash, -max)],
+                ),
+            ])
+            .unwrap_err();
+        assert!(matches!(err, ErrorKind::Overflow));
+        assert_eq!(ledger.journal().len(), 2);
+        assert_eq!(ledger.balance(Account::Available(2), usd), Amount::new(0));
+        assert!(ledger.verify());
+    }
+
+    #[test]
+    #[should_panic(expected = "unbalanced journal entry for tx 1")]
+    fn rejects_unbalanced_entries() {
+        let mut ledger = Ledger::new();
+        let _ = ledger.post(JournalEntry {
+            tx: 1,
+            postings: vec![posting(Account::Available(1), 10000)],
+        });
+    }
+
+    #[test]
+    fn formatting_accounts() {
+        assert_eq!(Account::Available(3).to_string(), "client:3:available");
+        assert_eq!(Account::Held(3).to_string(), "client:3:held");
+        assert_eq!(Account::Cash.to_string(), "system:cash");
+        assert_eq!(
+            Account::ChargebackLoss.to_string(),
+            "system:chargeback_loss"
+        );
+    }
+}
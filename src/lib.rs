@@ -0,0 +1,36 @@
+//! Payments engine that applies deposits, withdrawals and disputes to client accounts.
+//!
+//! Transactions are fed into a [`Database`] one at a time, either directly or through
+//! [`input::read_csv`], and the resulting balances are available as [`ClientEntry`] values.
+
+pub mod amount;
+mod client;
+pub mod config;
+pub mod currency;
+mod database;
+pub mod dispute;
+pub mod error;
+pub mod fee;
+pub mod input;
+pub mod ledger;
+mod outcome;
+pub mod sort;
+mod transaction;
+
+pub use amount::Amount;
+pub use client::ClientEntry;
+pub use config::Config;
+pub use currency::Currency;
+pub use database::{AuditEntry, Database};
+pub use dispute::TransactionState;
+pub use error::{Error, ErrorKind};
+pub use fee::{FeeEntry, FeeSchedule};
+pub use ledger::Ledger;
+pub use outcome::{Outcome, RejectReason};
+pub use sort::SortOrder;
+pub use transaction::{Record, Transaction};
+
+/// Identifier of a client account.
+pub type ClientId = u16;
+/// Globally unique identifier of a transaction.
+pub type TransactionId = u32;
@@ -1,136 +1,185 @@
-use std::collections::HashMap;
-use std::error::Error;
-use std::io::Write;
-use std::io::{stdout, BufWriter};
-use std::process;
-use std::{
-    fs::File,
-    io::BufReader,
-    path::{Path, PathBuf},
-};
-
-use csv::{ReaderBuilder, Trim};
-use serde::Deserialize;
-
-mod amount;
-use amount::Amount;
-
-mod client;
-use client::{Client, ClientTransaction};
+mod cli;
+
+use std::fs::File;
+use std::io::{stdin, stdout, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use cli::{Args, Command, LogLevel, OutputFormat, Parsed, Status};
+use rust_challenge_csv::input::{read_inputs, Input, ReadOptions, ReadSummary};
+use rust_challenge_csv::{Database, Error, SortOrder};
+
+/// Opens all inputs before reading any of them, `-` standing for stdin.
+fn open_inputs(paths: &[String]) -> Result<Vec<Input<Box<dyn Read>>>, Error> {
+    paths
+        .iter()
+        .map(|path| {
+            let reader: Box<dyn Read> = if path == "-" {
+                Box::new(stdin().lock())
+            } else {
+                let f = File::open(path).map_err(|err| Error::from(err).with_input(path))?;
+                Box::new(BufReader::new(f))
+            };
+            Ok(Input::new(path, reader))
+        })
+        .collect()
+}
 
-type ClientId = u16;
-type TransactionId = u32;
+fn read_input(
+    paths: &[String],
+    database: &mut Database,
+    options: &ReadOptions,
+) -> Result<ReadSummary, Error> {
+    let inputs = open_inputs(paths)?;
+    read_inputs(inputs, database, options)
+}
 
-enum Transaction {
-    Deposit(ClientId, TransactionId, Amount),
-    Withdrawal(ClientId, TransactionId, Amount),
-    Dispute(ClientId, TransactionId),
-    Resolve(ClientId, TransactionId),
-    Chargeback(ClientId, TransactionId),
+fn open_output(path: Option<&Path>) -> Result<Box<dyn Write>, Box<dyn std::error::Error>> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(stdout())),
+    })
 }
 
-impl Transaction {
-    pub fn from_record(record: Record) -> Self {
-        match record.r#type.as_str() {
-            "deposit" => Transaction::Deposit(record.client, record.tx, record.amount.unwrap()),
-            "withdrawal" => {
-                Transaction::Withdrawal(record.client, record.tx, record.amount.unwrap())
-            }
-            "dispute" => Transaction::Dispute(record.client, record.tx),
-            "resolve" => Transaction::Resolve(record.client, record.tx),
-            "chargeback" => Transaction::Chargeback(record.client, record.tx),
-            _ => unreachable!(),
+fn write_output(
+    database: &Database,
+    args: &Args,
+    summary: &ReadSummary,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Validating writes nothing, so an existing output is left as it is.
+    if args.command == Command::Validate {
+        return Ok(());
+    }
+    let mut writer = open_output(args.output.as_deref())?;
+    match (args.command, args.format) {
+        (Command::Process, OutputFormat::Csv) => database.write_csv(
+            &mut writer,
+            args.read.delimiter,
+            &args.order,
+            args.with_currency,
+        )?,
+        (Command::Process, OutputFormat::Jsonl) => {
+            database.write_jsonl(&mut writer, &args.order)?
         }
+        (Command::Stats, _) => write_stats(&mut writer, summary)?,
+        (Command::Validate, _) => unreachable!("nothing to write"),
     }
+    writer.flush()?;
+    Ok(())
 }
 
-#[derive(Debug, Deserialize)]
-struct Record {
-    r#type: String,
-    client: ClientId,
-    tx: TransactionId,
-    amount: Option<Amount>,
-}
-
-struct Database {
-    clients: HashMap<ClientId, Client>,
+fn write_stats(writer: &mut dyn Write, summary: &ReadSummary) -> std::io::Result<()> {
+    writeln!(writer, "rows: {}", summary.rows)?;
+    writeln!(writer, "invalid: {}", summary.invalid)?;
+    for (r#type, count) in &summary.types {
+        writeln!(writer, "{}: {}", r#type, count)?;
+    }
+    writeln!(writer, "rejected: {}", summary.rejected_total())?;
+    for (reason, count) in &summary.rejected {
+        writeln!(writer, "rejected ({}): {}", reason, count)?;
+    }
+    writeln!(writer, "duplicates: {}", summary.duplicates)?;
+    writeln!(writer, "partial holds: {}", summary.partial_holds)
 }
 
-impl Database {
-    pub fn new() -> Self {
-        Self {
-            clients: HashMap::new(),
-        }
+fn report(summary: &ReadSummary) {
+    if summary.invalid > 0 {
+        eprintln!("invalid {} of {} input rows", summary.invalid, summary.rows);
     }
-
-    pub fn add_transaction(&mut self, tx: Transaction) {
-        let (client_id, client_tx) = match tx {
-            Transaction::Deposit(client_id, tx_id, amount) => {
-                (client_id, ClientTransaction::deposit(tx_id, amount))
-            }
-            Transaction::Withdrawal(client_id, tx_id, amount) => {
-                (client_id, ClientTransaction::withdrawal(tx_id, amount))
-            }
-            Transaction::Dispute(client_id, tx_id) => {
-                (client_id, ClientTransaction::dispute(tx_id))
-            }
-            Transaction::Resolve(client_id, tx_id) => {
-                (client_id, ClientTransaction::resolve(tx_id))
-            }
-            Transaction::Chargeback(client_id, tx_id) => {
-                (client_id, ClientTransaction::chargeback(tx_id))
-            }
-        };
-
-        if let Some(client) = self.clients.get_mut(&client_id) {
-            client.add_transaction(client_tx)
-        } else {
-            let mut client = Client::new(client_id);
-            client.add_transaction(client_tx);
-            self.clients.insert(client_id, client);
-        }
+    for (reason, count) in &summary.rejected {
+        eprintln!("rejected {} transactions: {}", count, reason);
     }
-
-    pub fn write_all(&self, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
-        writeln!(writer, "client,available,held,total,locked")?;
-        for client in self.clients.values() {
-            let entry = client.get_entry();
-            writeln!(writer, "{}", entry)?;
-        }
-        Ok(())
+    if summary.duplicates > 0 {
+        eprintln!("dropped {} duplicate transactions", summary.duplicates);
+    }
+    if summary.partial_holds > 0 {
+        eprintln!(
+            "held {} disputes only in part due to overdraft limits",
+            summary.partial_holds
+        );
     }
 }
 
-fn read_input_csv(path: &Path, database: &mut Database) -> Result<(), Box<dyn Error>> {
-    let f = File::open(path)?;
-    let reader = BufReader::new(f);
-    let mut rdr = ReaderBuilder::new().trim(Trim::All).from_reader(reader);
-    for result in rdr.deserialize() {
-        let record: Record = result?;
-        database.add_transaction(Transaction::from_record(record));
+fn report_exposure(database: &Database) {
+    let mut entries: Vec<_> = database.negative_exposure().collect();
+    SortOrder::default().sort(&mut entries);
+    for entry in entries {
+        eprintln!(
+            "client {} has negative available funds: {} {}",
+            entry.id(),
+            entry.available(),
+            entry.currency()
+        );
     }
-    Ok(())
 }
 
-fn write_output(database: &Database) -> Result<(), Box<dyn Error>> {
-    let mut writer = BufWriter::new(stdout());
-    database.write_all(&mut writer)?;
-    Ok(())
+fn main() -> ExitCode {
+    ExitCode::from(run() as u8)
 }
 
-fn main() {
-    let path = std::env::args().nth(1).expect("no path given");
-    let path = PathBuf::from(path);
+/// Runs the command line and returns its status, writing all diagnostics to stderr so that
+/// they never mix with the output.
+fn run() -> Status {
+    let args = match cli::parse(std::env::args().skip(1)) {
+        Ok(Parsed::Run(args)) => args,
+        Ok(Parsed::Help) => {
+            print!("{}", cli::HELP);
+            return Status::Success;
+        }
+        Err(err) => {
+            eprintln!("{}\n\nRun with `--help` to see the available options.", err);
+            return Status::Usage;
+        }
+    };
+    let log = |level: LogLevel| args.log_level >= level;
+
+    let mut database = Database::with_config(args.config.clone());
 
-    let mut database = Database::new();
+    let summary = match read_input(&args.inputs, &mut database, &args.read) {
+        Ok(summary) => summary,
+        Err(err) => {
+            if log(LogLevel::Error) {
+                eprintln!("error reading input: {}", err);
+            }
+            return Status::of_error(&err);
+        }
+    };
+    if log(LogLevel::Warn) {
+        if args.command != Command::Stats {
+            report(&summary);
+        }
+        report_exposure(&database);
+    }
+    if log(LogLevel::Info) {
+        eprintln!(
+            "read {} rows from {} inputs, {} valid",
+            summary.rows,
+            args.inputs.len(),
+            summary.rows - summary.invalid
+        );
+    }
 
-    if let Err(err) = read_input_csv(&path, &mut database) {
-        println!("error reading input csv file: {}", err);
-        process::exit(1);
+    // Checked before the output is opened, so that an existing `--output` is kept.
+    if args.command == Command::Process && args.format == OutputFormat::Csv && !args.with_currency {
+        let mut entries: Vec<_> = database.foreign_balances().collect();
+        SortOrder::default().sort(&mut entries);
+        if let Some(entry) = entries.first() {
+            if log(LogLevel::Error) {
+                eprintln!(
+                    "balance of client {} in {} needs a currency column, run with `--with-currency`",
+                    entry.id(),
+                    entry.currency()
+                );
+            }
+            return Status::Usage;
+        }
     }
 
-    if let Err(err) = write_output(&database) {
-        println!("error writing output: {}", err);
-        process::exit(1);
+    if let Err(err) = write_output(&database, &args, &summary) {
+        if log(LogLevel::Error) {
+            eprintln!("error writing output: {}", err);
+        }
+        return Status::Io;
     }
+    Status::of_summary(args.command, &summary)
 }
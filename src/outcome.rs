@@ -0,0 +1,86 @@
+use std::fmt;
+
+use crate::Amount;
+
+/// Result of applying a well-formed transaction to the [`Database`](crate::Database).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The transaction took effect.
+    Applied,
+    /// The transaction was valid input but did not take effect, for the given reason.
+    Rejected(RejectReason),
+    /// The dispute took effect, but only the given part of the amount could be held because
+    /// of the [`OverdraftPolicy`](crate::config::OverdraftPolicy).
+    PartiallyHeld(Amount),
+    /// The transaction repeats an earlier one and was dropped, see
+    /// [`DuplicatePolicy::Deduplicate`](crate::config::DuplicatePolicy::Deduplicate).
+    Duplicate,
+}
+
+/// Reason why a transaction did not take effect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RejectReason {
+    /// A deposit, withdrawal or transfer of zero, or a dispute, resolve or chargeback of a
+    /// zero part of a transaction.
+    ZeroAmount,
+    /// A deposit, withdrawal or transfer of a negative amount, which would move funds the
+    /// other way, or a dispute, resolve or chargeback of a negative part of a transaction.
+    NegativeAmount,
+    /// A transfer from a client to itself.
+    SelfTransfer,
+    /// A withdrawal or transfer over the available funds.
+    InsufficientFunds,
+    /// A transaction that is not allowed on a locked account by the
+    /// [`LockPolicy`](crate::config::LockPolicy).
+    AccountLocked,
+    /// An unlock of an account that is not locked.
+    NotLocked,
+    /// A dispute, resolve or chargeback referring to a transaction that does not exist.
+    UnknownTx,
+    /// A resolve or chargeback of a transaction that is not under dispute.
+    NotDisputed,
+    /// A dispute of more than the part of a transaction not yet disputed, or a resolve or
+    /// chargeback of more than the disputed part.
+    ExceedsDisputed,
+    /// A dispute of a transaction that is already under dispute.
+    AlreadyDisputed,
+    /// A dispute of funds disputed before not allowed by the
+    /// [`RedisputePolicy`](crate::dispute::RedisputePolicy).
+    RedisputeNotAllowed,
+    /// A dispute of a transaction that was already charged back.
+    AlreadyChargedBack,
+    /// A dispute that would push the available funds below the overdraft limit.
+    OverdraftLimit,
+    /// A dispute of a transfer or unlock, or of a transaction the
+    /// [`DisputePolicy`](crate::dispute::DisputePolicy) does not allow to be disputed.
+    NotDisputable,
+    /// A deposit, withdrawal, transfer or unlock reusing the id of an earlier transaction.
+    DuplicateTxId,
+    /// A dispute, resolve or chargeback naming a different client than the one that owns
+    /// the transaction.
+    ClientMismatch,
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            RejectReason::ZeroAmount => "zero amount",
+            RejectReason::NegativeAmount => "negative amount",
+            RejectReason::SelfTransfer => "transfer to the same client",
+            RejectReason::InsufficientFunds => "insufficient funds",
+            RejectReason::AccountLocked => "account locked",
+            RejectReason::NotLocked => "account not locked",
+            RejectReason::UnknownTx => "unknown transaction",
+            RejectReason::NotDisputed => "transaction not disputed",
+            RejectReason::ExceedsDisputed => "amount exceeds the disputable part",
+            RejectReason::AlreadyDisputed => "transaction already disputed",
+            RejectReason::RedisputeNotAllowed => "transaction cannot be disputed again",
+            RejectReason::AlreadyChargedBack => "transaction already charged back",
+            RejectReason::OverdraftLimit => "overdraft limit exceeded",
+            RejectReason::NotDisputable => "transaction cannot be disputed",
+            RejectReason::DuplicateTxId => "duplicate transaction id",
+            RejectReason::ClientMismatch => "transaction belongs to another client",
+        };
+        write!(f, "{}", reason)
+    }
+}
@@ -0,0 +1,178 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use crate::ClientEntry;
+
+/// Column of the output balances can be sorted by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortField {
+    Client,
+    Currency,
+    Available,
+    Held,
+    Total,
+    /// Unlocked accounts come before locked ones in ascending order.
+    Locked,
+}
+
+/// A field along with the direction to sort it in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+impl SortKey {
+    fn compare(&self, a: &ClientEntry, b: &ClientEntry) -> Ordering {
+        let ordering = match self.field {
+            SortField::Client => a.id().cmp(&b.id()),
+            SortField::Currency => a.currency().cmp(&b.currency()),
+            SortField::Available => a.available().cmp(&b.available()),
+            SortField::Held => a.held().cmp(&b.held()),
+            SortField::Total => a.total().cmp(&b.total()),
+            SortField::Locked => a.locked().cmp(&b.locked()),
+        };
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, descending) = match s.split_once(':') {
+            None => (s, false),
+            Some((field, "asc")) => (field, false),
+            Some((field, "desc")) => (field, true),
+            Some(_) => {
+                return Err(format!(
+                    "invalid sort key `{}`, expected `<field>[:asc|:desc]`",
+                    s
+                ))
+            }
+        };
+        let field = match field {
+            "client" => SortField::Client,
+            "currency" => SortField::Currency,
+            "available" => SortField::Available,
+            "held" => SortField::Held,
+            "total" => SortField::Total,
+            "locked" => SortField::Locked,
+            _ => {
+                return Err(format!(
+                    "invalid sort field `{}`, expected `client`, `currency`, `available`, `held`, `total` or `locked`",
+                    field
+                ))
+            }
+        };
+        Ok(Self { field, descending })
+    }
+}
+
+/// Order of the output balances, by a list of keys each breaking the ties of the ones
+/// before it.
+///
+/// Balances equal in all keys are ordered by client and currency, so the order is the same
+/// on every run. Without keys that is the whole order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SortOrder {
+    pub keys: Vec<SortKey>,
+}
+
+impl SortOrder {
+    pub fn sort(&self, entries: &mut [ClientEntry]) {
+        entries.sort_by(|a, b| {
+            self.keys
+                .iter()
+                .map(|key| key.compare(a, b))
+                .chain([a.id().cmp(&b.id()), a.currency().cmp(&b.currency())])
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    /// Parses comma separated keys, such as `locked:desc,total:desc`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys = s.split(',').map(str::parse).collect::<Result<_, _>>()?;
+        Ok(Self { keys })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Amount, Currency};
+
+    #[test]
+    fn parsing() {
+        assert_eq!(
+            "locked:desc,total".parse(),
+            Ok(SortOrder {
+                keys: vec![
+                    SortKey {
+                        field: SortField::Locked,
+                        descending: true
+                    },
+                    SortKey {
+                        field: SortField::Total,
+                        descending: false
+                    },
+                ]
+            })
+        );
+        assert_eq!(
+            "held:asc".parse::<SortKey>().map(|key| key.descending),
+            Ok(false)
+        );
+        assert!("total:down".parse::<SortOrder>().is_err());
+        assert!("name".parse::<SortOrder>().is_err());
+        assert!("".parse::<SortOrder>().is_err());
+    }
+
+    #[test]
+    fn sorting() {
+        let usd = Currency::default();
+        let jpy: Currency = "JPY".parse().unwrap();
+        let entry = |id, currency, available, locked| {
+            ClientEntry::new(id, currency, Amount::new(available), Amount::new(0), locked)
+        };
+        let entries = [
+            entry(3, usd, 10000, false),
+            entry(1, usd, 20000, true),
+            entry(2, usd, 10000, true),
+            entry(1, jpy, 10000, false),
+        ];
+        let sorted = |order: &str| {
+            let mut sorted = entries;
+            order.parse::<SortOrder>().unwrap().sort(&mut sorted);
+            sorted
+                .iter()
+                .map(|entry| format!("{} {}", entry.id(), entry.currency()))
+                .collect::<Vec<_>>()
+        };
+
+        let mut by_client = entries;
+        SortOrder::default().sort(&mut by_client);
+        assert_eq!(
+            by_client.map(|entry| (entry.id(), entry.currency())),
+            [(1, jpy), (1, usd), (2, usd), (3, usd)]
+        );
+        assert_eq!(sorted("total:desc"), ["1 USD", "1 JPY", "2 USD", "3 USD"]);
+        assert_eq!(
+            sorted("locked:desc,total"),
+            ["2 USD", "1 USD", "1 JPY", "3 USD"]
+        );
+        assert_eq!(
+            sorted("currency,client:desc"),
+            ["1 JPY", "3 USD", "2 USD", "1 USD"]
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use serde::Deserialize;
+
+use crate::amount::MAX_SCALE;
+use crate::{Amount, ClientId, Config, Currency, Error, ErrorKind, TransactionId};
+
+/// A single transaction as applied to the [`Database`](crate::Database).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Transaction {
+    /// Credits the amount to the client's available funds in the currency.
+    Deposit(ClientId, TransactionId, Amount, Currency),
+    /// Debits the amount from the client's available funds in the currency, if there are
+    /// enough of them and the account is not locked.
+    Withdrawal(ClientId, TransactionId, Amount, Currency),
+    /// Claims that an earlier deposit or withdrawal was erroneous and holds its amount, in
+    /// its currency, until the dispute is resolved or charged back. With an amount only that
+    /// part is disputed, which must not exceed the part not yet disputed. Amounts of records
+    /// keep the scale they are written with, the [`Database`](crate::Database) brings them to
+    /// the scale of the disputed transaction's currency.
+    Dispute(ClientId, TransactionId, Option<Amount>),
+    /// Releases the funds held by disputes back to the client, all of them or the given
+    /// amount.
+    Resolve(ClientId, TransactionId, Option<Amount>),
+    /// Reverses a disputed transaction, all of the disputed part or the given amount,
+    /// withdrawing the held funds and locking the account.
+    Chargeback(ClientId, TransactionId, Option<Amount>),
+    /// Moves the amount in the currency from the available funds of the first client to
+    /// those of the second one, or rejects it as a whole. Transfers cannot be disputed.
+    Transfer(ClientId, TransactionId, ClientId, Amount, Currency),
+    /// Reinstates a locked account after review. The reason is kept in the
+    /// [audit log](crate::Database::audit_log).
+    Unlock(ClientId, TransactionId, String),
+}
+
+impl Transaction {
+    /// Validates a record with the default [`Config`].
+    pub fn from_record(record: Record) -> Result<Self, Error> {
+        Self::from_record_with_config(record, &Config::default())
+    }
+
+    /// Validates a record, taking the currency of rows without one and the decimal places
+    /// of amounts from `config`.
+    pub fn from_record_with_config(record: Record, config: &Config) -> Result<Self, Error> {
+        let context = |kind| Error::new(kind).with_transaction(record.client, record.tx);
+        let currency = || -> Result<Currency, Error> {
+            match record.currency.as_deref() {
+                None | Some("") => Ok(config.currency),
+                Some(currency) => currency
+                    .parse()
+                    .map_err(|_| context(ErrorKind::InvalidCurrency(String::from(currency)))),
+            }
+        };
+        let amount = |currency| -> Result<Amount, Error> {
+            let amount = record
+                .amount
+                .as_deref()
+                .ok_or_else(|| context(ErrorKind::MissingAmount))?;
+            config
+                .precision
+                .parse(amount, currency)
+                .map_err(|err| context(ErrorKind::Amount(err)))
+        };
+        // Disputes name no currency of their own, an amount given is kept at the scale it
+        // is written with until the database brings it to the currency of the disputed
+        // transaction.
+        let partial = || -> Result<Option<Amount>, Error> {
+            match record.amount.as_deref() {
+                None | Some("") => Ok(None),
+                Some(amount) => {
+                    let written = amount
+                        .trim()
+                        .split_once('.')
+                        .map_or(0, |(_, fract)| fract.len());
+                    let scale = MAX_SCALE.min(written.try_into().unwrap_or(MAX_SCALE));
+                    Amount::parse(amount, scale, config.precision.excess)
+                        .map(Some)
+                        .map_err(|err| context(ErrorKind::Amount(err)))
+                }
+            }
+        };
+        let tx = match record.r#type.as_str() {
+            "deposit" => {
+                let currency = currency()?;
+                Transaction::Deposit(record.client, record.tx, amount(currency)?, currency)
+            }
+            "withdrawal" => {
+                let currency = currency()?;
+                Transaction::Withdrawal(record.client, record.tx, amount(currency)?, currency)
+            }
+            "transfer" => {
+                let to = record
+                    .to_client
+                    .ok_or_else(|| context(ErrorKind::MissingRecipient))?;
+                let currency = currency()?;
+                Transaction::Transfer(record.client, record.tx, to, amount(currency)?, currency)
+            }
+            "dispute" => Transaction::Dispute(record.client, record.tx, partial()?),
+            "resolve" => Transaction::Resolve(record.client, record.tx, partial()?),
+            "chargeback" => Transaction::Chargeback(record.client, record.tx, partial()?),
+            "unlock" => {
+                let reason = record
+                    .reason
+                    .clone()
+                    .filter(|reason| !reason.is_empty())
+                    .ok_or_else(|| context(ErrorKind::MissingReason))?;
+                Transaction::Unlock(record.client, record.tx, reason)
+            }
+            other => return Err(context(ErrorKind::UnknownType(String::from(other)))),
+        };
+        Ok(tx)
+    }
+
+    /// Returns the type as named in the `type` column.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Transaction::Deposit(..) => "deposit",
+            Transaction::Withdrawal(..) => "withdrawal",
+            Transaction::Dispute(..) => "dispute",
+            Transaction::Resolve(..) => "resolve",
+            Transaction::Chargeback(..) => "chargeback",
+            Transaction::Transfer(..) => "transfer",
+            Transaction::Unlock(..) => "unlock",
+        }
+    }
+
+    pub fn client(&self) -> ClientId {
+        match *self {
+            Transaction::Deposit(client, ..)
+            | Transaction::Withdrawal(client, ..)
+            | Transaction::Dispute(client, ..)
+            | Transaction::Resolve(client, ..)
+            | Transaction::Chargeback(client, ..)
+            | Transaction::Unlock(client, ..)
+            | Transaction::Transfer(client, ..) => client,
+        }
+    }
+
+    pub fn tx(&self) -> TransactionId {
+        match *self {
+            Transaction::Deposit(_, tx, ..)
+            | Transaction::Withdrawal(_, tx, ..)
+            | Transaction::Dispute(_, tx, _)
+            | Transaction::Resolve(_, tx, _)
+            | Transaction::Chargeback(_, tx, _)
+            | Transaction::Unlock(_, tx, ..)
+            | Transaction::Transfer(_, tx, ..) => tx,
+        }
+    }
+}
+
+/// A raw input row, before its type and amount have been validated.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Record {
+    pub r#type: String,
+    pub client: ClientId,
+    pub tx: TransactionId,
+    pub amount: Option<String>,
+    /// Currency of a deposit, withdrawal or transfer, the configured default if missing or
+    /// empty.
+    pub currency: Option<String>,
+    /// Client receiving a transfer, whose sender is given by `client`.
+    pub to_client: Option<ClientId>,
+    /// Audit reason, required for unlocks.
+    pub reason: Option<String>,
+    /// Position of the row among all inputs, required when they are merged by sequence.
+    pub seq: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::{ExcessPrecision, ParseAmountError, RoundingMode};
+    use crate::config::Precision;
+    use std::collections::HashMap;
+
+    fn record(r#type: &str, amount: Option<&str>) -> Record {
+        Record {
+            r#type: String::from(r#type),
+            client: 1,
+            tx: 2,
+            amount: amount.map(String::from),
+            currency: None,
+            to_client: None,
+            reason: None,
+            seq: None,
+        }
+    }
+
+    #[test]
+    fn from_record() {
+        assert_eq!(
+            Transaction::from_record(record("deposit", Some("1.5"))).unwrap(),
+            Transaction::Deposit(1, 2, Amount::new(15000), Currency::default())
+        );
+        assert_eq!(
+            Transaction::from_record(record("withdrawal", Some("1"))).unwrap(),
+            Transaction::Withdrawal(1, 2, Amount::new(10000), Currency::default())
+        );
+        assert_eq!(
+            Transaction::from_record(record("dispute", None)).unwrap(),
+            Transaction::Dispute(1, 2, None)
+        );
+        assert_eq!(
+            Transaction::from_record(record("resolve", Some("1"))).unwrap(),
+            Transaction::Resolve(1, 2, Some(Amount::new(10000)))
+        );
+        // Partial amounts keep the scale they are written with.
+        let Transaction::Dispute(_, _, Some(partial)) =
+            Transaction::from_record(record("dispute", Some("0.00000001"))).unwrap()
+        else {
+            panic!("not a partial dispute");
+        };
+        assert_eq!(partial, Amount::with_scale(1, 8));
+        assert_eq!(partial.scale(), 8);
+        assert_eq!(
+            Transaction::from_record(record("dispute", Some(""))).unwrap(),
+            Transaction::Dispute(1, 2, None)
+        );
+        assert_eq!(
+            Transaction::from_record(record("chargeback", None)).unwrap(),
+            Transaction::Chargeback(1, 2, None)
+        );
+        let unlock = Record {
+            reason: Some(String::from("reviewed")),
+            ..record("unlock", None)
+        };
+        assert_eq!(
+            Transaction::from_record(unlock).unwrap(),
+            Transaction::Unlock(1, 2, String::from("reviewed"))
+        );
+        let transfer = Record {
+            to_client: Some(3),
+            ..record("transfer", Some("1"))
+        };
+        assert_eq!(
+            Transaction::from_record(transfer).unwrap(),
+            Transaction::Transfer(1, 2, 3, Amount::new(10000), Currency::default())
+        );
+        for r#type in ["deposit", "withdrawal", "dispute", "transfer"] {
+            let record = Record {
+                to_client: Some(3),
+                ..record(r#type, Some("1"))
+            };
+            assert_eq!(
+                Transaction::from_record(record).unwrap().type_name(),
+                r#type
+            );
+        }
+
+        let btc: Currency = "BTC".parse().unwrap();
+        let config = Config {
+            currency: btc,
+            precision: Precision {
+                scale: 2,
+                scales: HashMap::from([(btc, 8)]),
+                excess: ExcessPrecision::Round(RoundingMode::HalfUp),
+            },
+            ..Config::default()
+        };
+        assert_eq!(
+            Transaction::from_record_with_config(record("deposit", Some("1.000000005")), &config)
+                .unwrap(),
+            Transaction::Deposit(1, 2, Amount::with_scale(100000001, 8), btc)
+        );
+        let usd = Record {
+            currency: Some(String::from("usd")),
+            ..record("deposit", Some("1.005"))
+        };
+        assert_eq!(
+            Transaction::from_record_with_config(usd, &config).unwrap(),
+            Transaction::Deposit(1, 2, Amount::with_scale(101, 2), Currency::default())
+        );
+    }
+
+    #[test]
+    fn from_invalid_record() {
+        let err = Transaction::from_record(record("deposit", None)).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::MissingAmount));
+        assert_eq!((err.client(), err.tx()), (Some(1), Some(2)));
+
+        let err = Transaction::from_record(record("withdrawal", Some("1.2.3"))).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Amount(_)));
+
+        let config = Config {
+            precision: Precision {
+                scale: 0,
+                ..Precision::default()
+            },
+            ..Config::default()
+        };
+        let err = Transaction::from_record_with_config(record("deposit", Some("1.5")), &config)
+            .unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::Amount(ParseAmountError::TooManyDecimalPlaces(0))
+        ));
+
+        let euro = Record {
+            currency: Some(String::from("€")),
+            ..record("deposit", Some("1"))
+        };
+        let err = Transaction::from_record(euro).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidCurrency(c) if c == "€"));
+
+        let err = Transaction::from_record(record("dispute", Some("a"))).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Amount(_)));
+
+        let err = Transaction::from_record(record("transfer", Some("1"))).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::MissingRecipient));
+
+        let err = Transaction::from_record(record("unlock", None)).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::MissingReason));
+
+        let err = Transaction::from_record(record("refund", Some("1"))).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::UnknownType(t) if t == "refund"));
+    }
+}